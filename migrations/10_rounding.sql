@@ -0,0 +1,45 @@
+ALTER TABLE projects ADD COLUMN rounding_mode VARCHAR(255);
+ALTER TABLE projects ADD COLUMN rounding_interval INTEGER;
+
+INSERT INTO settings (key, value) VALUES ('rounding', '{"mode":"none","interval":15}');
+
+DROP TRIGGER projects_history_after_insert;
+DROP TRIGGER projects_history_after_update;
+DROP TRIGGER projects_history_after_delete;
+
+CREATE TRIGGER projects_history_after_insert AFTER INSERT ON projects
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('project', NEW.project_id, 'insert', json_object(
+        'project_id', NEW.project_id, 'name', NEW.name, 'archived', NEW.archived,
+        'color', NEW.color, 'estimate', NEW.estimate,
+        'rounding_mode', NEW.rounding_mode, 'rounding_interval', NEW.rounding_interval
+    ));
+END;
+
+CREATE TRIGGER projects_history_after_update AFTER UPDATE ON projects
+WHEN OLD.name IS NOT NEW.name OR OLD.archived IS NOT NEW.archived OR OLD.color IS NOT NEW.color
+    OR OLD.estimate IS NOT NEW.estimate OR OLD.rounding_mode IS NOT NEW.rounding_mode
+    OR OLD.rounding_interval IS NOT NEW.rounding_interval
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('project', NEW.project_id, 'update', json_object(
+        'project_id', OLD.project_id, 'name', OLD.name, 'archived', OLD.archived,
+        'color', OLD.color, 'estimate', OLD.estimate,
+        'rounding_mode', OLD.rounding_mode, 'rounding_interval', OLD.rounding_interval
+    ), json_object(
+        'project_id', NEW.project_id, 'name', NEW.name, 'archived', NEW.archived,
+        'color', NEW.color, 'estimate', NEW.estimate,
+        'rounding_mode', NEW.rounding_mode, 'rounding_interval', NEW.rounding_interval
+    ));
+END;
+
+CREATE TRIGGER projects_history_after_delete AFTER DELETE ON projects
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('project', OLD.project_id, 'delete', json_object(
+        'project_id', OLD.project_id, 'name', OLD.name, 'archived', OLD.archived,
+        'color', OLD.color, 'estimate', OLD.estimate,
+        'rounding_mode', OLD.rounding_mode, 'rounding_interval', OLD.rounding_interval
+    ));
+END;
@@ -0,0 +1,9 @@
+ALTER TABLE entries ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+-- Keep the current order, which follows the entry ids.
+UPDATE entries SET position = (
+    SELECT COUNT(*) FROM entries AS earlier
+    WHERE earlier.parent IS entries.parent AND earlier.entry_id < entries.entry_id
+);
+
+CREATE INDEX entries_position ON entries(parent, position);
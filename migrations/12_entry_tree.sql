@@ -0,0 +1,57 @@
+ALTER TABLE entries ADD COLUMN parent_entry INTEGER REFERENCES entries(entry_id) ON DELETE CASCADE;
+
+-- The parent of an entry is the closest entry above it in its block that is nested less deep.
+UPDATE entries SET parent_entry = (
+    SELECT earlier.entry_id FROM entries AS earlier
+    WHERE earlier.parent IS entries.parent
+    AND COALESCE(earlier.nesting, 0) < COALESCE(entries.nesting, 0)
+    AND (earlier.position < entries.position
+        OR (earlier.position = entries.position AND earlier.entry_id < entries.entry_id))
+    ORDER BY earlier.position DESC, earlier.entry_id DESC
+    LIMIT 1
+);
+
+CREATE INDEX entries_parent_entry ON entries(parent_entry);
+
+DROP TRIGGER entries_history_after_insert;
+DROP TRIGGER entries_history_after_update;
+DROP TRIGGER entries_history_after_delete;
+
+CREATE TRIGGER entries_history_after_insert AFTER INSERT ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('entry', NEW.entry_id, 'insert', json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'parent_entry', NEW.parent_entry,
+        'nesting', NEW.nesting, 'text', NEW.text, 'show_todo', NEW.show_todo,
+        'is_done', NEW.is_done, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_update
+AFTER UPDATE OF parent, parent_entry, nesting, text, show_todo, is_done, deleted_at ON entries
+WHEN OLD.parent IS NOT NEW.parent OR OLD.parent_entry IS NOT NEW.parent_entry
+    OR OLD.nesting IS NOT NEW.nesting OR OLD.text IS NOT NEW.text
+    OR OLD.show_todo IS NOT NEW.show_todo OR OLD.is_done IS NOT NEW.is_done
+    OR OLD.deleted_at IS NOT NEW.deleted_at
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('entry', NEW.entry_id, 'update', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'parent_entry', OLD.parent_entry,
+        'nesting', OLD.nesting, 'text', OLD.text, 'show_todo', OLD.show_todo,
+        'is_done', OLD.is_done, 'deleted_at', OLD.deleted_at
+    ), json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'parent_entry', NEW.parent_entry,
+        'nesting', NEW.nesting, 'text', NEW.text, 'show_todo', NEW.show_todo,
+        'is_done', NEW.is_done, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_delete AFTER DELETE ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('entry', OLD.entry_id, 'delete', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'parent_entry', OLD.parent_entry,
+        'nesting', OLD.nesting, 'text', OLD.text, 'show_todo', OLD.show_todo,
+        'is_done', OLD.is_done, 'deleted_at', OLD.deleted_at
+    ));
+END;
@@ -0,0 +1,58 @@
+ALTER TABLE entries ADD COLUMN migrated_from INTEGER REFERENCES entries(entry_id) ON DELETE SET NULL;
+ALTER TABLE entries ADD COLUMN migrated_at DATETIME;
+
+DROP TRIGGER entries_history_after_insert;
+DROP TRIGGER entries_history_after_update;
+DROP TRIGGER entries_history_after_delete;
+
+CREATE TRIGGER entries_history_after_insert AFTER INSERT ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('entry', NEW.entry_id, 'insert', json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'parent_entry', NEW.parent_entry,
+        'nesting', NEW.nesting, 'text', NEW.text, 'show_todo', NEW.show_todo,
+        'is_done', NEW.is_done, 'due_date', NEW.due_date,
+        'scheduled_date', NEW.scheduled_date, 'migrated_from', NEW.migrated_from,
+        'migrated_at', NEW.migrated_at, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_update
+AFTER UPDATE OF
+    parent, parent_entry, nesting, text, show_todo, is_done, due_date, scheduled_date,
+    migrated_from, migrated_at, deleted_at
+ON entries
+WHEN OLD.parent IS NOT NEW.parent OR OLD.parent_entry IS NOT NEW.parent_entry
+    OR OLD.nesting IS NOT NEW.nesting OR OLD.text IS NOT NEW.text
+    OR OLD.show_todo IS NOT NEW.show_todo OR OLD.is_done IS NOT NEW.is_done
+    OR OLD.due_date IS NOT NEW.due_date OR OLD.scheduled_date IS NOT NEW.scheduled_date
+    OR OLD.migrated_from IS NOT NEW.migrated_from OR OLD.migrated_at IS NOT NEW.migrated_at
+    OR OLD.deleted_at IS NOT NEW.deleted_at
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('entry', NEW.entry_id, 'update', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'parent_entry', OLD.parent_entry,
+        'nesting', OLD.nesting, 'text', OLD.text, 'show_todo', OLD.show_todo,
+        'is_done', OLD.is_done, 'due_date', OLD.due_date,
+        'scheduled_date', OLD.scheduled_date, 'migrated_from', OLD.migrated_from,
+        'migrated_at', OLD.migrated_at, 'deleted_at', OLD.deleted_at
+    ), json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'parent_entry', NEW.parent_entry,
+        'nesting', NEW.nesting, 'text', NEW.text, 'show_todo', NEW.show_todo,
+        'is_done', NEW.is_done, 'due_date', NEW.due_date,
+        'scheduled_date', NEW.scheduled_date, 'migrated_from', NEW.migrated_from,
+        'migrated_at', NEW.migrated_at, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_delete AFTER DELETE ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('entry', OLD.entry_id, 'delete', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'parent_entry', OLD.parent_entry,
+        'nesting', OLD.nesting, 'text', OLD.text, 'show_todo', OLD.show_todo,
+        'is_done', OLD.is_done, 'due_date', OLD.due_date,
+        'scheduled_date', OLD.scheduled_date, 'migrated_from', OLD.migrated_from,
+        'migrated_at', OLD.migrated_at, 'deleted_at', OLD.deleted_at
+    ));
+END;
@@ -0,0 +1,115 @@
+ALTER TABLE entries ADD COLUMN todo_state VARCHAR(255);
+ALTER TABLE entries ADD COLUMN closed_at DATETIME;
+
+UPDATE entries SET
+    todo_state = CASE
+        WHEN migrated_at IS NOT NULL THEN 'migrated'
+        WHEN NOT show_todo THEN NULL
+        WHEN is_done THEN 'done'
+        ELSE 'open'
+    END,
+    closed_at = CASE
+        WHEN show_todo AND is_done AND migrated_at IS NULL
+        THEN COALESCE(updated_at, DATETIME('now'))
+    END;
+
+CREATE TABLE todo_transitions (
+	transition_id INTEGER PRIMARY KEY,
+	entry_fk INTEGER NOT NULL,
+	from_state VARCHAR(255),
+	to_state VARCHAR(255),
+	changed_at DATETIME NOT NULL DEFAULT (DATETIME('now')),
+
+    FOREIGN KEY (entry_fk) REFERENCES entries(entry_id) ON DELETE CASCADE
+);
+
+CREATE INDEX todo_transitions_entry ON todo_transitions(entry_fk);
+
+-- The existing todos start out with a single transition into their current state.
+INSERT INTO todo_transitions (entry_fk, from_state, to_state, changed_at)
+SELECT entry_id, NULL, todo_state, COALESCE(migrated_at, updated_at, DATETIME('now'))
+FROM entries WHERE todo_state IS NOT NULL;
+
+DROP INDEX entries_open_todos;
+DROP TRIGGER entries_history_after_insert;
+DROP TRIGGER entries_history_after_update;
+DROP TRIGGER entries_history_after_delete;
+
+ALTER TABLE entries DROP COLUMN show_todo;
+ALTER TABLE entries DROP COLUMN is_done;
+ALTER TABLE entries DROP COLUMN migrated_at;
+
+CREATE INDEX entries_open_todos ON entries(due_date)
+WHERE todo_state IN ('open', 'in_progress', 'blocked');
+
+-- Every change of the todo state is recorded as a transition, and entries that reach done or
+-- cancelled get the moment they were closed.
+CREATE TRIGGER entries_todo_state_after_insert AFTER INSERT ON entries
+WHEN NEW.todo_state IS NOT NULL
+BEGIN
+    INSERT INTO todo_transitions (entry_fk, from_state, to_state)
+    VALUES (NEW.entry_id, NULL, NEW.todo_state);
+    UPDATE entries SET closed_at = CASE
+        WHEN NEW.todo_state IN ('done', 'cancelled') THEN DATETIME('now')
+    END
+    WHERE entry_id = NEW.entry_id;
+END;
+
+CREATE TRIGGER entries_todo_state_after_update AFTER UPDATE OF todo_state ON entries
+WHEN OLD.todo_state IS NOT NEW.todo_state
+BEGIN
+    INSERT INTO todo_transitions (entry_fk, from_state, to_state)
+    VALUES (NEW.entry_id, OLD.todo_state, NEW.todo_state);
+    UPDATE entries SET closed_at = CASE
+        WHEN NEW.todo_state IN ('done', 'cancelled') THEN DATETIME('now')
+    END
+    WHERE entry_id = NEW.entry_id;
+END;
+
+CREATE TRIGGER entries_history_after_insert AFTER INSERT ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('entry', NEW.entry_id, 'insert', json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'parent_entry', NEW.parent_entry,
+        'nesting', NEW.nesting, 'text', NEW.text, 'todo_state', NEW.todo_state,
+        'due_date', NEW.due_date, 'scheduled_date', NEW.scheduled_date,
+        'migrated_from', NEW.migrated_from, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_update
+AFTER UPDATE OF
+    parent, parent_entry, nesting, text, todo_state, due_date, scheduled_date, migrated_from,
+    deleted_at
+ON entries
+WHEN OLD.parent IS NOT NEW.parent OR OLD.parent_entry IS NOT NEW.parent_entry
+    OR OLD.nesting IS NOT NEW.nesting OR OLD.text IS NOT NEW.text
+    OR OLD.todo_state IS NOT NEW.todo_state
+    OR OLD.due_date IS NOT NEW.due_date OR OLD.scheduled_date IS NOT NEW.scheduled_date
+    OR OLD.migrated_from IS NOT NEW.migrated_from
+    OR OLD.deleted_at IS NOT NEW.deleted_at
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('entry', NEW.entry_id, 'update', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'parent_entry', OLD.parent_entry,
+        'nesting', OLD.nesting, 'text', OLD.text, 'todo_state', OLD.todo_state,
+        'due_date', OLD.due_date, 'scheduled_date', OLD.scheduled_date,
+        'migrated_from', OLD.migrated_from, 'deleted_at', OLD.deleted_at
+    ), json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'parent_entry', NEW.parent_entry,
+        'nesting', NEW.nesting, 'text', NEW.text, 'todo_state', NEW.todo_state,
+        'due_date', NEW.due_date, 'scheduled_date', NEW.scheduled_date,
+        'migrated_from', NEW.migrated_from, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_delete AFTER DELETE ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('entry', OLD.entry_id, 'delete', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'parent_entry', OLD.parent_entry,
+        'nesting', OLD.nesting, 'text', OLD.text, 'todo_state', OLD.todo_state,
+        'due_date', OLD.due_date, 'scheduled_date', OLD.scheduled_date,
+        'migrated_from', OLD.migrated_from, 'deleted_at', OLD.deleted_at
+    ));
+END;
@@ -0,0 +1,49 @@
+-- Full text indexes over the texts of blocks and entries, kept in sync by the triggers below.
+CREATE VIRTUAL TABLE blocks_search USING fts5(
+    text,
+    content = 'blocks',
+    content_rowid = 'block_id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE entries_search USING fts5(
+    text,
+    content = 'entries',
+    content_rowid = 'entry_id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO blocks_search (blocks_search) VALUES ('rebuild');
+INSERT INTO entries_search (entries_search) VALUES ('rebuild');
+
+CREATE TRIGGER blocks_search_after_insert AFTER INSERT ON blocks
+BEGIN
+    INSERT INTO blocks_search (rowid, text) VALUES (NEW.block_id, NEW.text);
+END;
+
+CREATE TRIGGER blocks_search_after_update AFTER UPDATE OF text ON blocks
+BEGIN
+    INSERT INTO blocks_search (blocks_search, rowid, text) VALUES ('delete', OLD.block_id, OLD.text);
+    INSERT INTO blocks_search (rowid, text) VALUES (NEW.block_id, NEW.text);
+END;
+
+CREATE TRIGGER blocks_search_after_delete AFTER DELETE ON blocks
+BEGIN
+    INSERT INTO blocks_search (blocks_search, rowid, text) VALUES ('delete', OLD.block_id, OLD.text);
+END;
+
+CREATE TRIGGER entries_search_after_insert AFTER INSERT ON entries
+BEGIN
+    INSERT INTO entries_search (rowid, text) VALUES (NEW.entry_id, NEW.text);
+END;
+
+CREATE TRIGGER entries_search_after_update AFTER UPDATE OF text ON entries
+BEGIN
+    INSERT INTO entries_search (entries_search, rowid, text) VALUES ('delete', OLD.entry_id, OLD.text);
+    INSERT INTO entries_search (rowid, text) VALUES (NEW.entry_id, NEW.text);
+END;
+
+CREATE TRIGGER entries_search_after_delete AFTER DELETE ON entries
+BEGIN
+    INSERT INTO entries_search (entries_search, rowid, text) VALUES ('delete', OLD.entry_id, OLD.text);
+END;
@@ -0,0 +1,19 @@
+-- Pauses overlapping each other could be subtracted twice, so never report less than nothing.
+DROP VIEW block_durations;
+
+CREATE VIEW block_durations AS
+SELECT
+    blocks.block_id,
+    MAX(0, COALESCE(STRFTIME('%s', blocks.end) - STRFTIME('%s', blocks.start), 0)
+    - COALESCE((
+        SELECT SUM(MAX(0,
+            STRFTIME('%s', MIN(COALESCE(pauses.end, blocks.end), blocks.end))
+            - STRFTIME('%s', MAX(pauses.start, blocks.start))
+        ))
+        FROM pauses WHERE pauses.block_fk = blocks.block_id
+    ), 0)) AS duration
+FROM blocks;
+
+UPDATE blocks SET duration = (
+    SELECT duration FROM block_durations WHERE block_durations.block_id = blocks.block_id
+);
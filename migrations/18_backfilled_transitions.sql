@@ -0,0 +1,23 @@
+-- The transitions 15_todo_states.sql made up for the existing todos, written before it was
+-- recorded as applied. Their time is only a guess, so reports leave them out.
+ALTER TABLE todo_transitions ADD COLUMN backfilled BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE todo_transitions SET backfilled = TRUE
+WHERE from_state IS NULL
+AND changed_at <= (SELECT installed_on FROM _sqlx_migrations WHERE version = 15)
+AND transition_id = (
+    SELECT MIN(first.transition_id) FROM todo_transitions AS first
+    WHERE first.entry_fk = todo_transitions.entry_fk
+);
+
+-- Todos closed before the todo states were added have no known closing time.
+UPDATE entries SET closed_at = NULL
+WHERE closed_at IS NOT NULL
+AND EXISTS (
+    SELECT 1 FROM todo_transitions
+    WHERE todo_transitions.entry_fk = entries.entry_id AND todo_transitions.backfilled
+)
+AND NOT EXISTS (
+    SELECT 1 FROM todo_transitions
+    WHERE todo_transitions.entry_fk = entries.entry_id AND NOT todo_transitions.backfilled
+);
@@ -0,0 +1,6 @@
+DELETE FROM tagged_blocks
+WHERE tagged_id NOT IN (
+    SELECT MIN(tagged_id) FROM tagged_blocks GROUP BY block_fk, tag_fk
+);
+
+CREATE UNIQUE INDEX tagged_blocks_block_tag ON tagged_blocks(block_fk, tag_fk);
@@ -0,0 +1,10 @@
+CREATE TABLE tagged_entries (
+	tagged_id INTEGER PRIMARY KEY,
+	entry_fk INTEGER NOT NULL,
+	tag_fk INTEGER NOT NULL,
+
+    FOREIGN KEY (entry_fk) REFERENCES entries(entry_id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_fk) REFERENCES tags(tag_id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX tagged_entries_entry_tag ON tagged_entries(entry_fk, tag_fk);
@@ -0,0 +1,61 @@
+CREATE TABLE pauses (
+	pause_id INTEGER PRIMARY KEY,
+	block_fk INTEGER NOT NULL,
+	start DATETIME NOT NULL,
+	end DATETIME,
+
+    FOREIGN KEY (block_fk) REFERENCES blocks(block_id) ON DELETE CASCADE
+);
+
+CREATE INDEX pauses_block ON pauses(block_fk);
+
+-- The net duration of a block: the time between start and end minus the paused time within it.
+-- Running blocks have a duration of 0, like before.
+CREATE VIEW block_durations AS
+SELECT
+    blocks.block_id,
+    COALESCE(STRFTIME('%s', blocks.end) - STRFTIME('%s', blocks.start), 0)
+    - COALESCE((
+        SELECT SUM(MAX(0,
+            STRFTIME('%s', MIN(COALESCE(pauses.end, blocks.end), blocks.end))
+            - STRFTIME('%s', MAX(pauses.start, blocks.start))
+        ))
+        FROM pauses WHERE pauses.block_fk = blocks.block_id
+    ), 0) AS duration
+FROM blocks;
+
+-- Keep blocks.duration equal to the net duration, whichever query changes the times.
+CREATE TRIGGER blocks_duration_after_insert AFTER INSERT ON blocks
+BEGIN
+    UPDATE blocks SET duration = (
+        SELECT duration FROM block_durations WHERE block_id = NEW.block_id
+    ) WHERE block_id = NEW.block_id;
+END;
+
+CREATE TRIGGER blocks_duration_after_update AFTER UPDATE OF start, end ON blocks
+BEGIN
+    UPDATE blocks SET duration = (
+        SELECT duration FROM block_durations WHERE block_id = NEW.block_id
+    ) WHERE block_id = NEW.block_id;
+END;
+
+CREATE TRIGGER pauses_duration_after_insert AFTER INSERT ON pauses
+BEGIN
+    UPDATE blocks SET duration = (
+        SELECT duration FROM block_durations WHERE block_id = NEW.block_fk
+    ) WHERE block_id = NEW.block_fk;
+END;
+
+CREATE TRIGGER pauses_duration_after_update AFTER UPDATE ON pauses
+BEGIN
+    UPDATE blocks SET duration = (
+        SELECT duration FROM block_durations WHERE block_id = NEW.block_fk
+    ) WHERE block_id = NEW.block_fk;
+END;
+
+CREATE TRIGGER pauses_duration_after_delete AFTER DELETE ON pauses
+BEGIN
+    UPDATE blocks SET duration = (
+        SELECT duration FROM block_durations WHERE block_id = OLD.block_fk
+    ) WHERE block_id = OLD.block_fk;
+END;
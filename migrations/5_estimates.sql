@@ -0,0 +1,2 @@
+ALTER TABLE blocks ADD COLUMN estimate INTEGER;
+ALTER TABLE projects ADD COLUMN estimate INTEGER;
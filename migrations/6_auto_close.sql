@@ -0,0 +1,2 @@
+ALTER TABLE blocks ADD COLUMN auto_closed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE entries ADD COLUMN updated_at DATETIME;
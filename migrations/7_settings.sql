@@ -0,0 +1,6 @@
+CREATE TABLE settings (
+	key VARCHAR(255) PRIMARY KEY,
+	value TEXT NOT NULL
+);
+
+INSERT INTO settings (key, value) VALUES ('timezone', '"UTC"');
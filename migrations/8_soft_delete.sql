@@ -0,0 +1,4 @@
+ALTER TABLE blocks ADD COLUMN deleted_at DATETIME;
+ALTER TABLE entries ADD COLUMN deleted_at DATETIME;
+
+INSERT INTO settings (key, value) VALUES ('trash_retention_days', '30');
@@ -0,0 +1,122 @@
+CREATE TABLE history (
+	history_id INTEGER PRIMARY KEY,
+	record_type VARCHAR(255) NOT NULL,
+	record_id INTEGER NOT NULL,
+	action VARCHAR(255) NOT NULL,
+	old_value TEXT,
+	new_value TEXT,
+	changed_at DATETIME NOT NULL DEFAULT (DATETIME('now'))
+);
+
+CREATE INDEX history_record ON history(record_type, record_id);
+
+-- Every change to blocks, entries and projects is recorded with a JSON snapshot of the row
+-- before and after, whichever query made the change. The duration of a block is left out
+-- because it follows from the other columns.
+CREATE TRIGGER blocks_history_after_insert AFTER INSERT ON blocks
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('block', NEW.block_id, 'insert', json_object(
+        'block_id', NEW.block_id, 'text', NEW.text, 'project', NEW.project,
+        'start', NEW.start, 'end', NEW.end, 'estimate', NEW.estimate,
+        'auto_closed', NEW.auto_closed, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER blocks_history_after_update
+AFTER UPDATE OF text, project, start, end, estimate, auto_closed, deleted_at ON blocks
+WHEN OLD.text IS NOT NEW.text OR OLD.project IS NOT NEW.project OR OLD.start IS NOT NEW.start
+    OR OLD.end IS NOT NEW.end OR OLD.estimate IS NOT NEW.estimate
+    OR OLD.auto_closed IS NOT NEW.auto_closed OR OLD.deleted_at IS NOT NEW.deleted_at
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('block', NEW.block_id, 'update', json_object(
+        'block_id', OLD.block_id, 'text', OLD.text, 'project', OLD.project,
+        'start', OLD.start, 'end', OLD.end, 'estimate', OLD.estimate,
+        'auto_closed', OLD.auto_closed, 'deleted_at', OLD.deleted_at
+    ), json_object(
+        'block_id', NEW.block_id, 'text', NEW.text, 'project', NEW.project,
+        'start', NEW.start, 'end', NEW.end, 'estimate', NEW.estimate,
+        'auto_closed', NEW.auto_closed, 'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER blocks_history_after_delete AFTER DELETE ON blocks
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('block', OLD.block_id, 'delete', json_object(
+        'block_id', OLD.block_id, 'text', OLD.text, 'project', OLD.project,
+        'start', OLD.start, 'end', OLD.end, 'estimate', OLD.estimate,
+        'auto_closed', OLD.auto_closed, 'deleted_at', OLD.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_insert AFTER INSERT ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('entry', NEW.entry_id, 'insert', json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'nesting', NEW.nesting,
+        'text', NEW.text, 'show_todo', NEW.show_todo, 'is_done', NEW.is_done,
+        'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_update
+AFTER UPDATE OF parent, nesting, text, show_todo, is_done, deleted_at ON entries
+WHEN OLD.parent IS NOT NEW.parent OR OLD.nesting IS NOT NEW.nesting OR OLD.text IS NOT NEW.text
+    OR OLD.show_todo IS NOT NEW.show_todo OR OLD.is_done IS NOT NEW.is_done
+    OR OLD.deleted_at IS NOT NEW.deleted_at
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('entry', NEW.entry_id, 'update', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'nesting', OLD.nesting,
+        'text', OLD.text, 'show_todo', OLD.show_todo, 'is_done', OLD.is_done,
+        'deleted_at', OLD.deleted_at
+    ), json_object(
+        'entry_id', NEW.entry_id, 'parent', NEW.parent, 'nesting', NEW.nesting,
+        'text', NEW.text, 'show_todo', NEW.show_todo, 'is_done', NEW.is_done,
+        'deleted_at', NEW.deleted_at
+    ));
+END;
+
+CREATE TRIGGER entries_history_after_delete AFTER DELETE ON entries
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('entry', OLD.entry_id, 'delete', json_object(
+        'entry_id', OLD.entry_id, 'parent', OLD.parent, 'nesting', OLD.nesting,
+        'text', OLD.text, 'show_todo', OLD.show_todo, 'is_done', OLD.is_done,
+        'deleted_at', OLD.deleted_at
+    ));
+END;
+
+CREATE TRIGGER projects_history_after_insert AFTER INSERT ON projects
+BEGIN
+    INSERT INTO history (record_type, record_id, action, new_value)
+    VALUES ('project', NEW.project_id, 'insert', json_object(
+        'project_id', NEW.project_id, 'name', NEW.name, 'archived', NEW.archived,
+        'color', NEW.color, 'estimate', NEW.estimate
+    ));
+END;
+
+CREATE TRIGGER projects_history_after_update AFTER UPDATE ON projects
+WHEN OLD.name IS NOT NEW.name OR OLD.archived IS NOT NEW.archived OR OLD.color IS NOT NEW.color
+    OR OLD.estimate IS NOT NEW.estimate
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value, new_value)
+    VALUES ('project', NEW.project_id, 'update', json_object(
+        'project_id', OLD.project_id, 'name', OLD.name, 'archived', OLD.archived,
+        'color', OLD.color, 'estimate', OLD.estimate
+    ), json_object(
+        'project_id', NEW.project_id, 'name', NEW.name, 'archived', NEW.archived,
+        'color', NEW.color, 'estimate', NEW.estimate
+    ));
+END;
+
+CREATE TRIGGER projects_history_after_delete AFTER DELETE ON projects
+BEGIN
+    INSERT INTO history (record_type, record_id, action, old_value)
+    VALUES ('project', OLD.project_id, 'delete', json_object(
+        'project_id', OLD.project_id, 'name', OLD.name, 'archived', OLD.archived,
+        'color', OLD.color, 'estimate', OLD.estimate
+    ));
+END;
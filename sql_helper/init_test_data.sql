@@ -22,27 +22,27 @@ VALUES
 (9, 'Bumping my head against timing issues', 1, DATETIME('2025-08-05T15:35:30'), NULL, 0);
 
 INSERT INTO entries
-(entry_id, parent, "nesting", "text", show_todo, is_done)
+(entry_id, parent, parent_entry, "nesting", "position", "text", todo_state)
 VALUES
-(1, 1, 0, 'US-01-Integration testing', 0, 0),
-(2, 1, 1, 'Use case 01.1 inserting new blocks', 1, 1),
-(3, 1, 1, 'Use case 01.2 inserting new entries', 1, 1),
-(4, 1, 1, 'Use case 01.3 adding tags', 1, 0),
-(5, 1, 1, 'Use case 01.4 linking blocks', 1, 0),
-(6, 2, 0, 'Ran into a problem with setting up the integration tests.', 0, 0),
-(7, 2, 0, 'When clicking anywhere over the full width of an empty line in logseq, it starts editing that line. This is functionality Im missing, so when the content is empty it is almost impossible to start typing in it.', 0, 0),
-(8, 2, 1, 'Integration testing is very difficult, the timing between all the services is hard to manage, and you are far removed from the thing you are testing. But it has also been really helpful in actually finding problems', 0, 0),
-(9, 2, 2, 'Got all the integration tests to work! (imagine)', 0, 0),
-(10, 4, 0, 'If only this code was rust, I would still not understand it', 0, 0),
-(11, 6, 0, 'Fill in hours', 1, 0),
-(12, 6, 0, 'Do that thing', 1, 0),
-(13, 7, 0, 'Trying to figure out in what order I should display blocks', 0, 0),
-(14, 7, 1, 'Option 1: Older lower, newer on the top', 0, 0),
-(15, 7, 1, 'Option 2: Normal writing direction, so oldest at the bottom', 0, 0),
-(16, 7, 2, 'This would feel weird with scrolling, but normal with reading', 0, 0),
-(17, 8, 0, 'Playing around with quoridor', 0, 0),
-(18, 8, 1, 'I want to build a system to quickly play quoridor against', 0, 0),
-(19, 9, 0, 'Argh why are integration tests so incredibly difficult to get correctly. I thought I figured them out, but there are still flaky tests. I am not entirely sure where the problem is.', 0, 0);
+(1, 1, NULL, 0, 0, 'US-01-Integration testing', NULL),
+(2, 1, 1, 1, 1, 'Use case 01.1 inserting new blocks', 'done'),
+(3, 1, 1, 1, 2, 'Use case 01.2 inserting new entries', 'done'),
+(4, 1, 1, 1, 3, 'Use case 01.3 adding tags', 'open'),
+(5, 1, 1, 1, 4, 'Use case 01.4 linking blocks', 'open'),
+(6, 2, NULL, 0, 0, 'Ran into a problem with setting up the integration tests.', NULL),
+(7, 2, NULL, 0, 1, 'When clicking anywhere over the full width of an empty line in logseq, it starts editing that line. This is functionality Im missing, so when the content is empty it is almost impossible to start typing in it.', NULL),
+(8, 2, 7, 1, 2, 'Integration testing is very difficult, the timing between all the services is hard to manage, and you are far removed from the thing you are testing. But it has also been really helpful in actually finding problems', NULL),
+(9, 2, 8, 2, 3, 'Got all the integration tests to work! (imagine)', NULL),
+(10, 4, NULL, 0, 0, 'If only this code was rust, I would still not understand it', NULL),
+(11, 6, NULL, 0, 0, 'Fill in hours', 'open'),
+(12, 6, NULL, 0, 1, 'Do that thing', 'open'),
+(13, 7, NULL, 0, 0, 'Trying to figure out in what order I should display blocks', NULL),
+(14, 7, 13, 1, 1, 'Option 1: Older lower, newer on the top', NULL),
+(15, 7, 13, 1, 2, 'Option 2: Normal writing direction, so oldest at the bottom', NULL),
+(16, 7, 15, 2, 3, 'This would feel weird with scrolling, but normal with reading', NULL),
+(17, 8, NULL, 0, 0, 'Playing around with quoridor', NULL),
+(18, 8, 17, 1, 1, 'I want to build a system to quickly play quoridor against', NULL),
+(19, 9, NULL, 0, 0, 'Argh why are integration tests so incredibly difficult to get correctly. I thought I figured them out, but there are still flaky tests. I am not entirely sure where the problem is.', NULL);
 
 INSERT INTO tags
 (tag_id, name, archived)
@@ -0,0 +1,206 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use sqlx::SqliteConnection;
+
+use crate::{
+    blocks::close_open_pause, database::Database, errors::AppError, settings::load_settings,
+};
+
+pub static AUTO_CLOSE: Lazy<AutoCloseConfig> = Lazy::new(AutoCloseConfig::from_env);
+
+/// Settings for closing running blocks that somebody forgot to stop.
+#[derive(Debug, Clone)]
+pub struct AutoCloseConfig {
+    /// Running blocks older than this are closed.
+    pub max_block_length: Duration,
+    /// Time of day at which the working day ends, used as cutoff when a block has no activity.
+    pub workday_end: NaiveTime,
+    /// How often the background task checks for blocks to close.
+    pub interval: StdDuration,
+}
+
+impl AutoCloseConfig {
+    pub fn from_env() -> Self {
+        let max_block_hours: i64 = dotenvy::var("MAX_BLOCK_HOURS")
+            .unwrap_or_else(|_| "12".to_string())
+            .parse()
+            .expect("MAX_BLOCK_HOURS needs to be a whole number of hours");
+        let workday_end = NaiveTime::parse_from_str(
+            &dotenvy::var("WORKDAY_END").unwrap_or_else(|_| "18:00".to_string()),
+            "%H:%M",
+        )
+        .expect("WORKDAY_END needs to be a time formatted as HH:MM");
+        let interval_minutes: u64 = dotenvy::var("AUTO_CLOSE_INTERVAL_MINUTES")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .expect("AUTO_CLOSE_INTERVAL_MINUTES needs to be a whole number of minutes");
+        Self {
+            max_block_length: Duration::hours(max_block_hours),
+            workday_end,
+            interval: StdDuration::from_secs(interval_minutes * 60),
+        }
+    }
+
+    /// The moment a forgotten block is considered to have ended.
+    ///
+    /// That is the last time one of its entries was edited, or otherwise the end of the local
+    /// working day it started on. The cutoff never lies beyond the maximum block length.
+    fn cutoff(
+        &self,
+        tz: Tz,
+        start: DateTime<Utc>,
+        last_activity: Option<DateTime<Utc>>,
+    ) -> DateTime<Utc> {
+        let workday_end = tz
+            .from_local_datetime(
+                &start
+                    .with_timezone(&tz)
+                    .date_naive()
+                    .and_time(self.workday_end),
+            )
+            .earliest()
+            .map_or(start, |workday_end| workday_end.with_timezone(&Utc));
+        let cutoff = match last_activity {
+            Some(last_activity) if last_activity > start => last_activity,
+            _ if workday_end > start => workday_end,
+            _ => start + self.max_block_length,
+        };
+        cutoff.min(start + self.max_block_length)
+    }
+}
+
+/// Periodically closes running blocks that exceed the maximum block length.
+pub async fn run(db: Arc<Database>) {
+    let config = &*AUTO_CLOSE;
+    tracing::info!(
+        "Auto closing blocks longer than: {:?}",
+        config.max_block_length
+    );
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        interval.tick().await;
+        let closed = match db.pool.acquire().await {
+            Ok(mut conn) => match load_settings(&mut conn).await {
+                Ok(settings) => {
+                    close_overlong_blocks(&mut conn, config, settings.timezone, Utc::now()).await
+                }
+                Err(error) => Err(error),
+            },
+            Err(error) => Err(error.into()),
+        };
+        match closed {
+            Ok(0) => {}
+            Ok(closed) => tracing::info!("Auto closed {} blocks", closed),
+            Err(error) => tracing::warn!("Auto closing blocks failed: {:?}", error),
+        }
+    }
+}
+
+/// Closes the running blocks that started more than the maximum block length before `now`,
+/// flagging them as auto closed. Returns the number of closed blocks.
+pub async fn close_overlong_blocks(
+    conn: &mut SqliteConnection,
+    config: &AutoCloseConfig,
+    tz: Tz,
+    now: DateTime<Utc>,
+) -> Result<usize, AppError> {
+    let overlong = sqlx::query_as::<_, (i64, DateTime<Utc>, Option<DateTime<Utc>>)>(
+        "
+    SELECT
+        blocks.block_id,
+        blocks.start,
+        MAX(entries.updated_at) AS last_activity
+    FROM blocks
+
+    LEFT JOIN entries ON entries.parent = blocks.block_id AND entries.deleted_at IS NULL
+
+    WHERE blocks.end IS NULL AND blocks.start < DATETIME(?1) AND blocks.deleted_at IS NULL
+    GROUP BY blocks.block_id;
+        ",
+    )
+    .bind(now - config.max_block_length)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (block_id, start, last_activity) in &overlong {
+        let end = config.cutoff(tz, *start, *last_activity);
+        tracing::info!("Auto close block {} at: {:?}", block_id, end);
+        sqlx::query(
+            "
+    UPDATE blocks
+    SET
+        end = DATETIME(?2),
+        duration = STRFTIME('%s', DATETIME(?2)) - STRFTIME('%s', blocks.start),
+        auto_closed = TRUE
+    WHERE block_id = ?1;
+            ",
+        )
+        .bind(block_id)
+        .bind(end)
+        .execute(&mut *conn)
+        .await?;
+        close_open_pause(&mut *conn, *block_id, end).await?;
+    }
+    Ok(overlong.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+
+    fn config(workday_end: &str) -> AutoCloseConfig {
+        AutoCloseConfig {
+            max_block_length: Duration::hours(12),
+            workday_end: NaiveTime::parse_from_str(workday_end, "%H:%M").unwrap(),
+            interval: StdDuration::from_secs(900),
+        }
+    }
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 8, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn last_activity_is_the_cutoff() {
+        let cutoff = config("18:00").cutoff(Berlin, utc(4, 7, 0), Some(utc(4, 11, 30)));
+        assert_eq!(cutoff, utc(4, 11, 30));
+    }
+
+    #[test]
+    fn activity_before_the_start_is_ignored() {
+        let cutoff = config("18:00").cutoff(Berlin, utc(4, 7, 0), Some(utc(4, 6, 0)));
+        assert_eq!(cutoff, utc(4, 16, 0));
+    }
+
+    #[test]
+    fn without_activity_the_local_workday_end_is_the_cutoff() {
+        // 18:00 in Berlin is 16:00 UTC in summer.
+        let cutoff = config("18:00").cutoff(Berlin, utc(4, 7, 0), None);
+        assert_eq!(cutoff, utc(4, 16, 0));
+    }
+
+    #[test]
+    fn block_started_after_workday_end_runs_for_the_maximum_length() {
+        let cutoff = config("18:00").cutoff(Berlin, utc(4, 19, 0), None);
+        assert_eq!(cutoff, utc(5, 7, 0));
+    }
+
+    #[test]
+    fn cutoff_never_exceeds_the_maximum_length() {
+        let cutoff = config("18:00").cutoff(Berlin, utc(4, 7, 0), Some(utc(5, 7, 0)));
+        assert_eq!(cutoff, utc(4, 19, 0));
+    }
+
+    #[test]
+    fn workday_end_in_a_skipped_hour_falls_back_to_the_maximum_length() {
+        // Berlin skips from 02:00 to 03:00 on 2025-03-30.
+        let start = Utc.with_ymd_and_hms(2025, 3, 29, 23, 30, 0).unwrap();
+        let cutoff = config("02:30").cutoff(Berlin, start, None);
+        assert_eq!(cutoff, start + Duration::hours(12));
+    }
+}
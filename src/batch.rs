@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    routing::post,
+    Json, Router,
+};
+use chrono_tz::Tz;
+use sqlx::SqliteConnection;
+
+use crate::{
+    auth::Claims,
+    blocks::{create_block, delete_block, select_block, update_block},
+    database::Database,
+    entries::{create_entry, delete_entry, select_entry, update_entry},
+    errors::AppError,
+    models::{BatchOperation, BatchRecord, BatchResult, OverlapMode, OverlapParams, RecordType},
+    projects::{delete_project, insert_project, select_project, update_project},
+    settings::Timezone,
+};
+
+pub fn batch_router() -> Router<Arc<Database>> {
+    Router::new().route("/", post(post_batch))
+}
+
+/// Applies the operations in order within a single transaction. If one of them fails, none of
+/// them are kept and the error reports the state of every operation.
+async fn post_batch(
+    _: Claims,
+    Timezone(tz): Timezone,
+    params: Query<OverlapParams>,
+    db: State<Arc<Database>>,
+    axum::extract::Json(operations): axum::extract::Json<Vec<BatchOperation>>,
+) -> Result<Json<Vec<BatchResult>>, AppError> {
+    tracing::info!("Applying batch of {} operations", operations.len());
+    let mut tx = db.pool.begin().await?;
+    let mut results = Vec::with_capacity(operations.len());
+    for (index, operation) in operations.iter().enumerate() {
+        match apply_operation(&mut tx, tz, operation, params.overlap).await {
+            Ok(result) => results.push(result),
+            Err(error) => {
+                tx.rollback().await?;
+                return Err(AppError::BatchFailed {
+                    index,
+                    operations: operations.len(),
+                    error: Box::new(error),
+                });
+            }
+        }
+    }
+    tx.commit().await?;
+
+    Ok(Json(results))
+}
+
+async fn apply_operation(
+    conn: &mut SqliteConnection,
+    tz: Tz,
+    operation: &BatchOperation,
+    overlap: OverlapMode,
+) -> Result<BatchResult, AppError> {
+    let saved = match operation {
+        BatchOperation::Create(BatchRecord::Block(block)) => {
+            let block_id = create_block(conn, tz, block, overlap).await?;
+            BatchRecord::Block(select_block(&mut *conn, block_id).await?.0)
+        }
+        BatchOperation::Create(BatchRecord::Entry(entry)) => {
+            let entry_id = create_entry(conn, entry).await?;
+            BatchRecord::Entry(select_entry(&mut *conn, entry_id).await?.0)
+        }
+        BatchOperation::Create(BatchRecord::Project(project)) => {
+            let project_id = insert_project(conn, project).await?;
+            BatchRecord::Project(select_project(&mut *conn, project_id).await?.0)
+        }
+        BatchOperation::Update(BatchRecord::Block(block)) => {
+            update_block(conn, block, overlap).await?;
+            BatchRecord::Block(select_block(&mut *conn, block.block_id).await?.0)
+        }
+        BatchOperation::Update(BatchRecord::Entry(entry)) => {
+            update_entry(conn, entry).await?;
+            BatchRecord::Entry(select_entry(&mut *conn, entry.entry_id).await?.0)
+        }
+        BatchOperation::Update(BatchRecord::Project(project)) => {
+            update_project(conn, project).await?;
+            BatchRecord::Project(select_project(&mut *conn, project.project_id).await?.0)
+        }
+        BatchOperation::Delete { record_type, id } => {
+            let deleted = match record_type {
+                RecordType::Block => delete_block(conn, *id).await?,
+                RecordType::Entry => delete_entry(conn, *id).await?,
+                RecordType::Project => delete_project(conn, *id).await?,
+            };
+            if !deleted {
+                return Err(AppError::NotFound);
+            }
+            return Ok(BatchResult::Deleted {
+                record_type: *record_type,
+                id: *id,
+            });
+        }
+    };
+    Ok(BatchResult::Saved(saved))
+}
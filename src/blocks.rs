@@ -4,38 +4,77 @@ use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, put},
+    routing::{delete, get, post, put},
     Json, Router,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{SqliteConnection, SqliteExecutor};
 
 use crate::{
     auth::Claims,
+    auto_close::{close_overlong_blocks, AUTO_CLOSE},
     database::Database,
+    entries::{renumber_entries, update_subtree_nesting},
     errors::AppError,
-    models::{Block, InsertResult, NextDataResponse, RangeParams},
+    models::{
+        Block, FilterParams, InsertResult, MergeBlocks, NextDataResponse, OverlapMode,
+        OverlapParams, Pause, PauseBlock, RangeParams, SplitBlock, StopBlock, TagName,
+    },
+    query::Filter,
+    settings::Timezone,
+    tags::{
+        copy_block_tags, merge_text_tags, set_block_tags, tag_block, tag_id_for_name, untag_block,
+    },
 };
 
 pub fn blocks_router() -> Router<Arc<Database>> {
     Router::new()
         .route("/", get(get_blocks).post(post_block))
+        .route("/current", get(get_current_block))
+        .route("/current/stop", post(stop_current_block))
+        .route("/merge", post(merge_blocks))
         .route("/{block_id}", put(put_block).delete(delete_block_api))
+        .route("/{block_id}/split", post(split_block))
+        .route("/{block_id}/pauses", get(get_pauses))
+        .route("/{block_id}/pause", post(pause_block))
+        .route("/{block_id}/resume", post(resume_block))
+        .route("/{block_id}/tags", post(add_block_tag))
+        .route("/{block_id}/tags/{tag_id}", delete(remove_block_tag))
         .route("/next_before/{last_data}", get(get_timestamp_next_block))
 }
 
 async fn get_blocks(
     _: Claims,
+    Timezone(tz): Timezone,
     params: Query<RangeParams>,
+    filter_params: Query<FilterParams>,
     db: State<Arc<Database>>,
 ) -> Result<Json<Vec<Block>>, AppError> {
+    let filter = filter_params.get_filter()?;
+    let (start, end) = params.get_filtered_range(tz, &filter);
     tracing::info!(
-        "Getting blocks between: {:?} and {:?}",
-        params.get_start(),
-        params.get_end()
+        "Getting blocks between: {:?} and {:?} matching: {:?}",
+        start,
+        end,
+        filter
     );
     Ok(Json(
-        sqlx::query_as::<_, Block>(
-            "
+        select_blocks_in_range(&db, start, end, &filter).await?,
+    ))
+}
+
+/// Selects the blocks that start from `start` up to, but not including, `end` and match
+/// `filter`. A missing bound leaves the range open on that side. Tags, todo states and texts
+/// of the filter match when either the block or one of its entries has them.
+pub(crate) async fn select_blocks_in_range(
+    db: &Database,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    filter: &Filter,
+) -> Result<Vec<Block>, AppError> {
+    Ok(sqlx::query_as::<_, Block>(
+        "
         SELECT
         	blocks.block_id,
         	blocks.text,
@@ -44,6 +83,9 @@ async fn get_blocks(
         	blocks.start,
         	blocks.end,
         	blocks.duration,
+        	COALESCE(STRFTIME('%s', blocks.end) - STRFTIME('%s', blocks.start), 0) AS gross_duration,
+        	blocks.estimate,
+        	blocks.auto_closed,
         	COALESCE(GROUP_CONCAT(DISTINCT tags.name), '') AS tags
         FROM blocks
 
@@ -51,32 +93,89 @@ async fn get_blocks(
         LEFT JOIN tagged_blocks ON blocks.block_id = tagged_blocks.block_fk
         LEFT JOIN tags ON tagged_blocks.tag_fk = tags.tag_id
 
-        WHERE blocks.start > DATETIME(?1) AND blocks.start < DATETIME(?2)
+        WHERE (?1 IS NULL OR blocks.start >= DATETIME(?1))
+        AND (?2 IS NULL OR blocks.start < DATETIME(?2))
+        AND blocks.deleted_at IS NULL
+        AND (?3 IS NULL OR LOWER(projects.name) IN (SELECT LOWER(value) FROM json_each(?3)))
+        AND (?4 IS NULL OR blocks.block_id IN (
+            SELECT filter_tagged.block_fk FROM tagged_blocks AS filter_tagged
+            JOIN tags AS filter_tags ON filter_tagged.tag_fk = filter_tags.tag_id
+            WHERE filter_tags.name IN (SELECT value FROM json_each(?4))
+            UNION
+            SELECT filter_entries.parent FROM entries AS filter_entries
+            JOIN tagged_entries AS filter_tagged ON filter_entries.entry_id = filter_tagged.entry_fk
+            JOIN tags AS filter_tags ON filter_tagged.tag_fk = filter_tags.tag_id
+            WHERE filter_tags.name IN (SELECT value FROM json_each(?4))
+            AND filter_entries.deleted_at IS NULL
+        ))
+        AND (?5 IS NULL OR EXISTS (
+            SELECT 1 FROM entries AS filter_entries
+            WHERE filter_entries.parent = blocks.block_id AND filter_entries.deleted_at IS NULL
+            AND filter_entries.todo_state IN (SELECT value FROM json_each(?5))
+        ))
+        AND (?6 IS NULL OR NOT EXISTS (
+            SELECT 1 FROM json_each(?6) AS words
+            WHERE INSTR(LOWER(blocks.text), LOWER(words.value)) = 0
+            AND NOT EXISTS (
+                SELECT 1 FROM entries AS filter_entries
+                WHERE filter_entries.parent = blocks.block_id
+                AND filter_entries.deleted_at IS NULL
+                AND INSTR(LOWER(filter_entries.text), LOWER(words.value)) > 0
+            )
+        ))
         GROUP BY blocks.block_id
         ORDER BY blocks.start;
             ",
-        )
-        .bind(params.get_start())
-        .bind(params.get_end())
-        .fetch_all(&db.pool)
-        .await?,
-    ))
+    )
+    .bind(start)
+    .bind(end)
+    .bind(filter.get_projects_json())
+    .bind(filter.get_tags_json())
+    .bind(filter.get_todo_states_json())
+    .bind(filter.get_texts_json())
+    .fetch_all(&db.pool)
+    .await?)
 }
 
 async fn post_block(
     _: Claims,
+    Timezone(tz): Timezone,
+    params: Query<OverlapParams>,
     db: State<Arc<Database>>,
     axum::extract::Json(block): axum::extract::Json<Block>,
 ) -> Result<Json<Block>, AppError> {
     tracing::info!("Inserting new block");
-    update_end_timestamps_of_unclosed_blocks(&db, &block).await?;
-    let new_block_id = insert_block(&db, block).await?;
-    select_block(&db, new_block_id).await
+    let mut tx = db.pool.begin().await?;
+    let new_block_id = create_block(&mut tx, tz, &block, params.overlap).await?;
+    tx.commit().await?;
+    select_block(&db.pool, new_block_id).await
+}
+
+/// Starts a new block, closing the blocks that are still running. Returns the new block's id.
+pub(crate) async fn create_block(
+    conn: &mut SqliteConnection,
+    tz: Tz,
+    block: &Block,
+    overlap: OverlapMode,
+) -> Result<i64, AppError> {
+    let span = BlockSpan {
+        block_id: None,
+        start: block.start,
+        end: None,
+    };
+    resolve_overlaps(conn, &span, Some(block.start), overlap).await?;
+    close_overlong_blocks(conn, &AUTO_CLOSE, tz, block.start).await?;
+    update_end_timestamps_of_unclosed_blocks(conn, block).await?;
+    let new_block_id = insert_block(conn, block).await?;
+    let tags = merge_text_tags(&block.tags, None, &block.text);
+    set_block_tags(conn, new_block_id, &tags).await?;
+    Ok(new_block_id)
 }
 
 async fn put_block(
     _: Claims,
     Path(block_id): Path<i64>,
+    params: Query<OverlapParams>,
     db: State<Arc<Database>>,
     axum::extract::Json(block): axum::extract::Json<Block>,
 ) -> Result<Json<Block>, AppError> {
@@ -84,14 +183,35 @@ async fn put_block(
         return Err(AppError::BadRequest);
     }
     tracing::info!("Put block: {:?}", block_id);
+    let mut tx = db.pool.begin().await?;
+    update_block(&mut tx, &block, params.overlap).await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, block.block_id).await
+}
+
+pub(crate) async fn update_block(
+    conn: &mut SqliteConnection,
+    block: &Block,
+    overlap: OverlapMode,
+) -> Result<(), AppError> {
+    let old_text = select_block_text(conn, block.block_id).await?;
+    let span = BlockSpan {
+        block_id: Some(block.block_id),
+        start: block.start,
+        end: block.end,
+    };
+    resolve_overlaps(conn, &span, None, overlap).await?;
     sqlx::query(
         "
         UPDATE blocks SET
             project=?2,
             start=DATETIME(?3),
             end=DATETIME(?4),
-            duration=STRFTIME('%s', DATETIME(?4)) - STRFTIME('%s', ?3),
-            text=?5
+            duration=COALESCE(STRFTIME('%s', DATETIME(?4)) - STRFTIME('%s', DATETIME(?3)), 0),
+            text=?5,
+            estimate=CASE WHEN ?8 THEN ?6 ELSE estimate END,
+            auto_closed=COALESCE(?7, auto_closed)
         WHERE block_id=?1;
             ",
     )
@@ -99,37 +219,591 @@ async fn put_block(
     .bind(block.project)
     .bind(block.start)
     .bind(block.end)
-    .bind(block.text)
-    .execute(&db.pool)
+    .bind(&block.text)
+    .bind(block.estimate.flatten())
+    .bind(block.auto_closed)
+    .bind(block.estimate.is_some())
+    .execute(&mut *conn)
     .await?;
+    let tags = merge_text_tags(&block.tags, Some(&old_text), &block.text);
+    set_block_tags(conn, block.block_id, &tags).await
+}
 
-    select_block(&db, block.block_id).await
+async fn get_current_block(
+    _: Claims,
+    db: State<Arc<Database>>,
+) -> Result<Json<Option<Block>>, AppError> {
+    tracing::info!("Get current block");
+    let mut conn = db.pool.acquire().await?;
+    match select_current_block_id(&mut conn).await? {
+        Some(block_id) => Ok(Json(Some(select_block(&db.pool, block_id).await?.0))),
+        None => Ok(Json(None)),
+    }
 }
 
-async fn delete_block_api(
+async fn stop_current_block(
+    _: Claims,
+    db: State<Arc<Database>>,
+    stop: Option<axum::extract::Json<StopBlock>>,
+) -> Result<Json<Block>, AppError> {
+    let end = stop.and_then(|stop| stop.end).unwrap_or_else(Utc::now);
+    tracing::info!("Stop current block at: {:?}", end);
+    let mut tx = db.pool.begin().await?;
+    let block_id = select_current_block_id(&mut tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let current = select_block_span(&mut tx, block_id).await?;
+    if end < current.start {
+        return Err(AppError::InvalidTimeRange);
+    }
+    if select_open_pause(&mut tx, block_id)
+        .await?
+        .is_some_and(|pause| end < pause.start)
+    {
+        return Err(AppError::InvalidTimeRange);
+    }
+    close_open_pause(&mut tx, block_id, end).await?;
+    sqlx::query(
+        "
+    UPDATE blocks
+    SET
+        end = DATETIME(?2),
+        duration = STRFTIME('%s', DATETIME(?2)) - STRFTIME('%s', blocks.start)
+    WHERE block_id = ?1;
+        ",
+    )
+    .bind(block_id)
+    .bind(end)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, block_id).await
+}
+
+async fn split_block(
     _: Claims,
     Path(block_id): Path<i64>,
     db: State<Arc<Database>>,
-) -> impl IntoResponse {
-    tracing::info!("Delete block: {}", block_id);
-    if sqlx::query(
+    axum::extract::Json(split): axum::extract::Json<SplitBlock>,
+) -> Result<Json<Vec<Block>>, AppError> {
+    tracing::info!("Split block {} at: {:?}", block_id, split.at);
+    let mut tx = db.pool.begin().await?;
+    let span = select_block_span(&mut tx, block_id).await?;
+    if split.at <= span.start || span.end.is_some_and(|end| split.at >= end) {
+        return Err(AppError::InvalidTimeRange);
+    }
+
+    let second_half = sqlx::query_as::<_, InsertResult>(
+        "
+    INSERT INTO blocks (
+        text,
+        project,
+        start,
+        end,
+        duration
+    )
+    SELECT
+        text,
+        project,
+        DATETIME(?2),
+        end,
+        COALESCE(STRFTIME('%s', end) - STRFTIME('%s', DATETIME(?2)), 0)
+    FROM blocks WHERE block_id = ?1
+    RETURNING block_id AS id;
+        ",
+    )
+    .bind(block_id)
+    .bind(split.at)
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+    sqlx::query(
+        "
+    INSERT INTO pauses (block_fk, start, end)
+    SELECT ?2, DATETIME(?3), end FROM pauses
+    WHERE block_fk = ?1 AND start < DATETIME(?3) AND (end IS NULL OR end > DATETIME(?3));
+
+    UPDATE pauses SET end = DATETIME(?3)
+    WHERE block_fk = ?1 AND start < DATETIME(?3) AND (end IS NULL OR end > DATETIME(?3));
+
+    UPDATE pauses SET block_fk = ?2
+    WHERE block_fk = ?1 AND start >= DATETIME(?3);
+        ",
+    )
+    .bind(block_id)
+    .bind(second_half)
+    .bind(split.at)
+    .execute(&mut *tx)
+    .await?;
+    update_block_span(&mut tx, block_id, span.start, Some(split.at)).await?;
+    copy_block_tags(&mut tx, block_id, second_half).await?;
+
+    let moved = sqlx::query(
+        "
+    UPDATE entries SET parent = ?2
+    WHERE parent = ?1 AND entry_id IN (SELECT value FROM json_each(?3));
+        ",
+    )
+    .bind(block_id)
+    .bind(second_half)
+    .bind(serde_json::to_string(&split.second_half_entries).map_err(|_| AppError::BadRequest)?)
+    .execute(&mut *tx)
+    .await?;
+    if moved.rows_affected() as usize != split.second_half_entries.len() {
+        return Err(AppError::BadRequest);
+    }
+    // Entries whose parent ended up in the other half move to the top of their own half.
+    let detached = sqlx::query_as::<_, InsertResult>(
         "
-        DELETE FROM blocks WHERE block_id = ?1;
+    UPDATE entries SET parent_entry = NULL
+    WHERE parent IN (?1, ?2) AND parent_entry IN (
+        SELECT above.entry_id FROM entries AS above
+        WHERE above.parent IN (?1, ?2) AND above.parent != entries.parent
+    )
+    RETURNING entry_id AS id;
+        ",
+    )
+    .bind(block_id)
+    .bind(second_half)
+    .fetch_all(&mut *tx)
+    .await?;
+    for entry in detached {
+        update_subtree_nesting(&mut tx, entry.id).await?;
+    }
+    renumber_entries(&mut tx, block_id).await?;
+    renumber_entries(&mut tx, second_half).await?;
+    tx.commit().await?;
+
+    Ok(Json(vec![
+        select_block(&db.pool, block_id).await?.0,
+        select_block(&db.pool, second_half).await?.0,
+    ]))
+}
+
+async fn merge_blocks(
+    _: Claims,
+    db: State<Arc<Database>>,
+    axum::extract::Json(merge): axum::extract::Json<MergeBlocks>,
+) -> Result<Json<Block>, AppError> {
+    tracing::info!("Merge blocks: {:?}", merge.block_ids);
+    let mut block_ids = merge.block_ids;
+    block_ids.sort_unstable();
+    block_ids.dedup();
+    if block_ids.len() < 2 {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut tx = db.pool.begin().await?;
+    let mut spans = Vec::with_capacity(block_ids.len());
+    for block_id in &block_ids {
+        spans.push(select_block_span(&mut tx, *block_id).await?);
+    }
+    spans.sort_by_key(|span| span.start);
+    let first = &spans[0];
+    let last = &spans[spans.len() - 1];
+    let block_ids_json = serde_json::to_string(&block_ids).map_err(|_| AppError::BadRequest)?;
+
+    let in_between = sqlx::query_as::<_, InsertResult>(
+        "
+    SELECT block_id AS id FROM blocks
+    WHERE block_id NOT IN (SELECT value FROM json_each(?1))
+    AND start >= DATETIME(?2) AND start <= DATETIME(?3)
+    AND deleted_at IS NULL
+    ORDER BY start;
+        ",
+    )
+    .bind(&block_ids_json)
+    .bind(first.start)
+    .bind(last.start)
+    .fetch_all(&mut *tx)
+    .await?;
+    if !in_between.is_empty() {
+        return Err(AppError::BlocksNotAdjacent(
+            in_between.into_iter().map(|block| block.id).collect(),
+        ));
+    }
+    let gaps = find_gaps(&spans);
+    if !gaps.is_empty() {
+        return Err(AppError::BlocksNotAdjacent(gaps));
+    }
+
+    let survivor = first.block_id.ok_or(AppError::InternalServer)?;
+    let end = spans
+        .iter()
+        .map(|span| span.end)
+        .reduce(|a, b| a.zip(b).map(|(a, b)| a.max(b)))
+        .flatten();
+    for span in &spans[1..] {
+        let block_id = span.block_id.ok_or(AppError::InternalServer)?;
+        copy_block_tags(&mut tx, block_id, survivor).await?;
+        // The entries of later blocks follow the entries already in the surviving block.
+        let offset = sqlx::query_as::<_, InsertResult>(
+            "
+    SELECT COALESCE(MAX(position) + 1, 0) AS id FROM entries WHERE parent = ?1;
             ",
+        )
+        .bind(survivor)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "
+    UPDATE entries SET parent = ?1, position = position + ?3 WHERE parent = ?2;
+            ",
+        )
+        .bind(survivor)
+        .bind(block_id)
+        .bind(offset.id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query(
+        "
+    UPDATE pauses SET block_fk = ?1
+    WHERE block_fk IN (SELECT value FROM json_each(?2)) AND block_fk != ?1;
+        ",
+    )
+    .bind(survivor)
+    .bind(&block_ids_json)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "
+    DELETE FROM blocks
+    WHERE block_id IN (SELECT value FROM json_each(?2)) AND block_id != ?1;
+        ",
+    )
+    .bind(survivor)
+    .bind(&block_ids_json)
+    .execute(&mut *tx)
+    .await?;
+    update_block_span(&mut tx, survivor, first.start, end).await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, survivor).await
+}
+
+async fn get_pauses(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<Pause>>, AppError> {
+    tracing::info!("Get pauses of block: {}", block_id);
+    Ok(Json(
+        sqlx::query_as::<_, Pause>(
+            "
+    SELECT
+        pause_id,
+        block_fk AS block_id,
+        start,
+        end
+    FROM pauses
+    WHERE block_fk = ?1
+    ORDER BY start;
+        ",
+        )
+        .bind(block_id)
+        .fetch_all(&db.pool)
+        .await?,
+    ))
+}
+
+async fn pause_block(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+    pause: Option<axum::extract::Json<PauseBlock>>,
+) -> Result<Json<Block>, AppError> {
+    let at = pause.and_then(|pause| pause.at).unwrap_or_else(Utc::now);
+    tracing::info!("Pause block {} at: {:?}", block_id, at);
+    let mut tx = db.pool.begin().await?;
+    let span = select_block_span(&mut tx, block_id).await?;
+    if at < span.start || span.end.is_some_and(|end| at >= end) {
+        return Err(AppError::InvalidTimeRange);
+    }
+    // The new pause stays open until resumed, so it may not start before any other one ended.
+    let later_pause = sqlx::query_as::<_, InsertResult>(
+        "
+    SELECT pause_id AS id FROM pauses
+    WHERE block_fk = ?1 AND (end IS NULL OR end > DATETIME(?2));
+        ",
     )
     .bind(block_id)
-    .execute(&db.pool)
-    .await
-    .is_ok()
-    {
+    .bind(at)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if later_pause.is_some() {
+        return Err(AppError::BadRequest);
+    }
+    sqlx::query(
+        "
+    INSERT INTO pauses (block_fk, start) VALUES (?1, DATETIME(?2));
+        ",
+    )
+    .bind(block_id)
+    .bind(at)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, block_id).await
+}
+
+async fn resume_block(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+    resume: Option<axum::extract::Json<PauseBlock>>,
+) -> Result<Json<Block>, AppError> {
+    let at = resume.and_then(|resume| resume.at).unwrap_or_else(Utc::now);
+    tracing::info!("Resume block {} at: {:?}", block_id, at);
+    let mut tx = db.pool.begin().await?;
+    let pause = select_open_pause(&mut tx, block_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if at < pause.start {
+        return Err(AppError::InvalidTimeRange);
+    }
+    let span = select_block_span(&mut tx, block_id).await?;
+    let at = span.end.map_or(at, |end| at.min(end));
+    sqlx::query(
+        "
+    UPDATE pauses SET end = DATETIME(?2) WHERE pause_id = ?1;
+        ",
+    )
+    .bind(pause.pause_id)
+    .bind(at)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, block_id).await
+}
+
+/// Ends a pause still open when its block stops, no earlier than the pause started.
+pub(crate) async fn close_open_pause(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+    end: DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    UPDATE pauses SET end = MAX(start, DATETIME(?2)) WHERE block_fk = ?1 AND end IS NULL;
+        ",
+    )
+    .bind(block_id)
+    .bind(end)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn select_open_pause(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+) -> Result<Option<Pause>, AppError> {
+    Ok(sqlx::query_as::<_, Pause>(
+        "
+    SELECT
+        pause_id,
+        block_fk AS block_id,
+        start,
+        end
+    FROM pauses
+    WHERE block_fk = ?1 AND end IS NULL;
+        ",
+    )
+    .bind(block_id)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+async fn add_block_tag(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+    axum::extract::Json(tag): axum::extract::Json<TagName>,
+) -> Result<Json<Block>, AppError> {
+    let name = tag.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest);
+    }
+    tracing::info!("Add tag {} to block: {}", name, block_id);
+    let mut tx = db.pool.begin().await?;
+    ensure_block_exists(&mut tx, block_id).await?;
+    let tag_id = tag_id_for_name(&mut tx, name).await?;
+    tag_block(&mut tx, block_id, tag_id).await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, block_id).await
+}
+
+async fn remove_block_tag(
+    _: Claims,
+    Path((block_id, tag_id)): Path<(i64, i64)>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Block>, AppError> {
+    tracing::info!("Remove tag {} from block: {}", tag_id, block_id);
+    let mut conn = db.pool.acquire().await?;
+    ensure_block_exists(&mut conn, block_id).await?;
+    untag_block(&mut conn, block_id, tag_id).await?;
+
+    select_block(&db.pool, block_id).await
+}
+
+async fn delete_block_api(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> impl IntoResponse {
+    tracing::info!("Delete block: {}", block_id);
+    let deleted = match db.pool.acquire().await {
+        Ok(mut conn) => delete_block(&mut conn, block_id).await,
+        Err(error) => Err(error.into()),
+    };
+    if deleted.is_ok() {
         (StatusCode::NO_CONTENT, "Block deleted")
     } else {
         (StatusCode::CONFLICT, "The block could not be deleted")
     }
 }
 
+/// Moves a block to the trash. Returns whether there was a block to delete.
+pub(crate) async fn delete_block(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "
+        UPDATE blocks SET deleted_at = DATETIME('now')
+        WHERE block_id = ?1 AND deleted_at IS NULL;
+            ",
+    )
+    .bind(block_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The time range a block occupies, `end` being `None` while the block is still running.
+pub(crate) struct BlockSpan {
+    pub(crate) block_id: Option<i64>,
+    pub(crate) start: DateTime<Utc>,
+    pub(crate) end: Option<DateTime<Utc>>,
+}
+
+/// The blocks among `spans`, ordered by start, that do not end where the next one starts.
+fn find_gaps(spans: &[BlockSpan]) -> Vec<i64> {
+    spans
+        .windows(2)
+        .filter(|pair| pair[0].end != Some(pair[1].start))
+        .filter_map(|pair| pair[0].block_id)
+        .collect()
+}
+
+/// Makes sure the span has a valid time range and does not overlap other blocks.
+///
+/// Running blocks are treated as running forever, unless `closing_at` is given: that is the
+/// moment they will be closed by the write, so they only conflict when they start after it.
+/// With [`OverlapMode::Trim`] the neighbours are shortened so the span fits between them. A
+/// neighbour that would disappear completely is still reported as a conflict.
+pub(crate) async fn resolve_overlaps(
+    conn: &mut SqliteConnection,
+    span: &BlockSpan,
+    closing_at: Option<DateTime<Utc>>,
+    mode: OverlapMode,
+) -> Result<(), AppError> {
+    if span.end.is_some_and(|end| end < span.start) {
+        return Err(AppError::InvalidTimeRange);
+    }
+    let neighbours = sqlx::query_as::<_, (i64, DateTime<Utc>, Option<DateTime<Utc>>)>(
+        "
+    SELECT block_id, start, end FROM blocks
+    WHERE (?1 IS NULL OR block_id != ?1)
+    AND deleted_at IS NULL
+    AND (?3 IS NULL OR start < DATETIME(?3))
+    AND CASE
+        WHEN end IS NOT NULL THEN end > DATETIME(?2)
+        WHEN ?4 IS NOT NULL THEN start >= DATETIME(?4)
+        ELSE TRUE
+    END
+    ORDER BY start;
+        ",
+    )
+    .bind(span.block_id)
+    .bind(span.start)
+    .bind(span.end)
+    .bind(closing_at)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut conflicts = Vec::new();
+    for (block_id, start, end) in neighbours {
+        let trimmed = match mode {
+            OverlapMode::Reject => None,
+            OverlapMode::Trim if start < span.start => Some((start, Some(span.start))),
+            OverlapMode::Trim => match span.end {
+                Some(span_end) if end.is_none_or(|end| end > span_end) => Some((span_end, end)),
+                _ => None,
+            },
+        };
+        match trimmed {
+            Some((start, end)) => update_block_span(conn, block_id, start, end).await?,
+            None => conflicts.push(block_id),
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(AppError::OverlappingBlocks(conflicts));
+    }
+    Ok(())
+}
+
+async fn update_block_span(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    tracing::info!("Trim block {} to: {:?} - {:?}", block_id, start, end);
+    sqlx::query(
+        "
+    UPDATE blocks
+    SET
+        start = DATETIME(?2),
+        end = DATETIME(?3),
+        duration = COALESCE(STRFTIME('%s', DATETIME(?3)) - STRFTIME('%s', DATETIME(?2)), 0)
+    WHERE block_id = ?1;
+        ",
+    )
+    .bind(block_id)
+    .bind(start)
+    .bind(end)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn select_block_span(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+) -> Result<BlockSpan, AppError> {
+    let (start, end) = sqlx::query_as::<_, (DateTime<Utc>, Option<DateTime<Utc>>)>(
+        "
+    SELECT start, end FROM blocks WHERE block_id = ?1 AND deleted_at IS NULL;
+        ",
+    )
+    .bind(block_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(BlockSpan {
+        block_id: Some(block_id),
+        start,
+        end,
+    })
+}
+
 async fn update_end_timestamps_of_unclosed_blocks(
-    db: &Database,
+    conn: &mut SqliteConnection,
     block: &Block,
 ) -> Result<(), AppError> {
     sqlx::query(
@@ -138,40 +812,88 @@ async fn update_end_timestamps_of_unclosed_blocks(
     SET
         end = DATETIME(?1),
         duration = STRFTIME('%s', DATETIME(?1)) - STRFTIME('%s', blocks.start)
-    WHERE end IS NULL;
+    WHERE end IS NULL AND deleted_at IS NULL;
        ",
     )
     .bind(block.start)
-    .execute(&db.pool)
+    .execute(&mut *conn)
     .await?;
     Ok(())
 }
 
-async fn insert_block(db: &Database, block: Block) -> Result<i64, AppError> {
+async fn insert_block(conn: &mut SqliteConnection, block: &Block) -> Result<i64, AppError> {
     let new_block_id = sqlx::query_as::<_, InsertResult>(
         "
         INSERT INTO blocks (
             text,
             project,
             start,
-            duration
+            duration,
+            estimate
         ) VALUES (
             ?1,
             ?2,
             DATETIME(?3),
-            0
+            0,
+            ?4
         ) RETURNING block_id AS id;
             ",
     )
-    .bind(block.text)
+    .bind(&block.text)
     .bind(block.project)
     .bind(block.start)
-    .fetch_one(&db.pool)
+    .bind(block.estimate.flatten())
+    .fetch_one(&mut *conn)
     .await?;
     Ok(new_block_id.id)
 }
 
-async fn select_block(db: &Database, block_id: i64) -> Result<Json<Block>, AppError> {
+pub(crate) async fn select_current_block_id(
+    conn: &mut SqliteConnection,
+) -> Result<Option<i64>, AppError> {
+    Ok(sqlx::query_as::<_, InsertResult>(
+        "
+    SELECT block_id AS id FROM blocks
+    WHERE end IS NULL AND deleted_at IS NULL
+    ORDER BY start DESC LIMIT 1;
+        ",
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|current| current.id))
+}
+
+async fn select_block_text(conn: &mut SqliteConnection, block_id: i64) -> Result<String, AppError> {
+    let (text,) = sqlx::query_as::<_, (String,)>(
+        "
+    SELECT text FROM blocks WHERE block_id = ?1 AND deleted_at IS NULL;
+        ",
+    )
+    .bind(block_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(text)
+}
+
+pub(crate) async fn ensure_block_exists(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query_as::<_, InsertResult>(
+        "
+    SELECT block_id AS id FROM blocks WHERE block_id = ?1 AND deleted_at IS NULL;
+        ",
+    )
+    .bind(block_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn select_block<'c>(
+    executor: impl SqliteExecutor<'c>,
+    block_id: i64,
+) -> Result<Json<Block>, AppError> {
     Ok(Json(
         sqlx::query_as::<_, Block>(
             "
@@ -183,18 +905,21 @@ async fn select_block(db: &Database, block_id: i64) -> Result<Json<Block>, AppEr
        	blocks.start,
        	blocks.end,
        	blocks.duration,
+       	COALESCE(STRFTIME('%s', blocks.end) - STRFTIME('%s', blocks.start), 0) AS gross_duration,
+       	blocks.estimate,
+       	blocks.auto_closed,
         COALESCE(GROUP_CONCAT(DISTINCT tags.name), '') AS tags
     FROM blocks
 
     LEFT OUTER JOIN projects ON blocks.project = projects.project_id
     LEFT JOIN tagged_blocks ON blocks.block_id = tagged_blocks.block_fk
     LEFT JOIN tags ON tagged_blocks.tag_fk = tags.tag_id
-    WHERE blocks.block_id = ?1
+    WHERE blocks.block_id = ?1 AND blocks.deleted_at IS NULL
     GROUP BY blocks.block_id;
         ",
         )
         .bind(block_id)
-        .fetch_one(&db.pool)
+        .fetch_one(executor)
         .await?,
     ))
 }
@@ -208,12 +933,54 @@ async fn get_timestamp_next_block(
     let next_data = sqlx::query_as::<_, NextDataResponse>(
         "
         SELECT start AS block_timestamp FROM blocks
-        WHERE start < DATETIME(?1)
+        WHERE start < DATETIME(?1) AND deleted_at IS NULL
         ORDER BY block_timestamp DESC LIMIT 1;
             ",
     )
-    .bind(&last_data.naive_utc())
+    .bind(last_data.naive_utc())
     .fetch_one(&db.pool)
     .await?;
     Ok(Json(next_data))
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn span(block_id: i64, start: u32, end: Option<u32>) -> BlockSpan {
+        let at = |hour| Utc.with_ymd_and_hms(2025, 8, 4, hour, 0, 0).unwrap();
+        BlockSpan {
+            block_id: Some(block_id),
+            start: at(start),
+            end: end.map(at),
+        }
+    }
+
+    #[test]
+    fn touching_blocks_have_no_gaps() {
+        let spans = [
+            span(1, 9, Some(10)),
+            span(2, 10, Some(12)),
+            span(3, 12, None),
+        ];
+        assert!(find_gaps(&spans).is_empty());
+    }
+
+    #[test]
+    fn gap_between_blocks() {
+        let spans = [
+            span(1, 9, Some(10)),
+            span(2, 11, Some(12)),
+            span(3, 12, Some(13)),
+        ];
+        assert_eq!(find_gaps(&spans), vec![1]);
+    }
+
+    #[test]
+    fn running_block_must_be_last() {
+        let spans = [span(1, 9, None), span(2, 10, Some(12))];
+        assert_eq!(find_gaps(&spans), vec![1]);
+    }
+}
@@ -1,69 +1,184 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, put},
+    routing::{get, post, put},
     Json, Router,
 };
+use chrono::NaiveDateTime;
+use sqlx::{SqliteConnection, SqliteExecutor};
 
 use crate::{
     auth::Claims,
+    blocks::ensure_block_exists,
     database::Database,
     errors::AppError,
-    models::{Entry, InsertResult, RangeParams},
+    models::{
+        Entry, EntryTree, FilterParams, InsertResult, MoveEntry, MoveToBlock, RangeParams,
+        TagParams, TodoState,
+    },
+    query::Filter,
+    settings::Timezone,
+    tags::{copy_entry_tags, merge_text_tags, set_entry_tags},
 };
 
 pub fn entries_router() -> Router<Arc<Database>> {
     Router::new()
         .route("/", get(get_entries).post(post_entry))
         .route("/{entry_id}", put(put_entry).delete(delete_entry_api))
+        .route("/tree", get(get_entry_trees))
+        .route("/{entry_id}/move", post(move_entry))
+        .route("/{entry_id}/move_to_block", post(move_entry_to_block))
+        .route("/{entry_id}/indent", post(indent_entry))
+        .route("/{entry_id}/outdent", post(outdent_entry))
+        .route("/{entry_id}/duplicate", post(duplicate_entry))
 }
 
 async fn get_entries(
     _: Claims,
+    Timezone(tz): Timezone,
     params: Query<RangeParams>,
+    tag_params: Query<TagParams>,
+    filter_params: Query<FilterParams>,
     db: State<Arc<Database>>,
 ) -> Result<Json<Vec<Entry>>, AppError> {
+    let mut filter = filter_params.get_filter()?;
+    let (start, end) = params.get_filtered_range(tz, &filter);
+    filter.tags.extend(tag_params.get_tags());
     tracing::info!(
-        "Getting entries between: {:?} and {:?}",
-        params.get_start(),
-        params.get_end()
+        "Getting entries between: {:?} and {:?} matching: {:?}",
+        start,
+        end,
+        filter
     );
     Ok(Json(
-        sqlx::query_as::<_, Entry>(
-            "
+        select_entries_in_range(&db, start, end, &filter).await?,
+    ))
+}
+
+/// Selects the entries of the blocks that start from `start` up to, but not including, `end`
+/// and that match `filter`. A missing bound leaves the range open on that side. The project
+/// of an entry is the project of its block.
+pub(crate) async fn select_entries_in_range(
+    db: &Database,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    filter: &Filter,
+) -> Result<Vec<Entry>, AppError> {
+    Ok(sqlx::query_as::<_, Entry>(
+        "
         WITH entries_for_range AS (
             SELECT
                 blocks.block_id as parent,
                 entries.entry_id,
+                entries.parent_entry,
                 entries.nesting,
                 entries.text,
-                entries.show_todo,
-                entries.is_done
+                entries.todo_state,
+                entries.closed_at,
+                entries.position,
+                entries.due_date,
+                entries.scheduled_date,
+                entries.migrated_from,
+                COALESCE(GROUP_CONCAT(DISTINCT tags.name), '') AS tags
             FROM blocks
 
+            LEFT OUTER JOIN projects ON blocks.project = projects.project_id
             LEFT JOIN entries ON entries.parent = blocks.block_id
+            LEFT JOIN tagged_entries ON entries.entry_id = tagged_entries.entry_fk
+            LEFT JOIN tags ON tagged_entries.tag_fk = tags.tag_id
 
-            WHERE blocks.start > DATETIME(?1) AND blocks.start < DATETIME(?2)
+            WHERE (?1 IS NULL OR blocks.start >= DATETIME(?1))
+            AND (?2 IS NULL OR blocks.start < DATETIME(?2))
+            AND blocks.deleted_at IS NULL AND entries.deleted_at IS NULL
+            AND (?3 IS NULL OR LOWER(projects.name) IN (SELECT LOWER(value) FROM json_each(?3)))
+            AND (?4 IS NULL OR entries.entry_id IN (
+                SELECT filter_tagged.entry_fk FROM tagged_entries AS filter_tagged
+                JOIN tags AS filter_tags ON filter_tagged.tag_fk = filter_tags.tag_id
+                WHERE filter_tags.name IN (SELECT value FROM json_each(?4))
+            ))
+            AND (?5 IS NULL OR entries.todo_state IN (SELECT value FROM json_each(?5)))
+            AND (?6 IS NULL OR NOT EXISTS (
+                SELECT 1 FROM json_each(?6) AS words
+                WHERE INSTR(LOWER(entries.text), LOWER(words.value)) = 0
+            ))
 
             GROUP BY
                 blocks.block_id,
                 entries.entry_id
             ORDER BY
                 blocks.block_id,
+                entries.position,
                 entries.entry_id
         )
     	SELECT * FROM entries_for_range
     	WHERE entries_for_range.entry_id IS NOT NULL;
             ",
-        )
-        .bind(params.get_start())
-        .bind(params.get_end())
-        .fetch_all(&db.pool)
-        .await?,
-    ))
+    )
+    .bind(start)
+    .bind(end)
+    .bind(filter.get_projects_json())
+    .bind(filter.get_tags_json())
+    .bind(filter.get_todo_states_json())
+    .bind(filter.get_texts_json())
+    .fetch_all(&db.pool)
+    .await?)
+}
+
+async fn get_entry_trees(
+    _: Claims,
+    Timezone(tz): Timezone,
+    params: Query<RangeParams>,
+    tag_params: Query<TagParams>,
+    filter_params: Query<FilterParams>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<EntryTree>>, AppError> {
+    let mut filter = filter_params.get_filter()?;
+    let (start, end) = params.get_filtered_range(tz, &filter);
+    filter.tags.extend(tag_params.get_tags());
+    tracing::info!(
+        "Getting entry trees between: {:?} and {:?} matching: {:?}",
+        start,
+        end,
+        filter
+    );
+    let entries = select_entries_in_range(&db, start, end, &filter).await?;
+    Ok(Json(build_trees(entries)))
+}
+
+/// Nests the entries under their parent entries, keeping their order. Entries whose parent
+/// entry is not among `entries` become roots.
+pub(crate) fn build_trees(entries: Vec<Entry>) -> Vec<EntryTree> {
+    let ids: HashSet<i64> = entries.iter().map(|entry| entry.entry_id).collect();
+    let mut roots = Vec::new();
+    let mut children: HashMap<i64, Vec<Entry>> = HashMap::new();
+    for entry in entries {
+        match entry.parent_entry {
+            Some(Some(parent_entry)) if ids.contains(&parent_entry) => {
+                children.entry(parent_entry).or_default().push(entry)
+            }
+            _ => roots.push(entry),
+        }
+    }
+    fn grow(entry: Entry, children: &mut HashMap<i64, Vec<Entry>>) -> EntryTree {
+        let nested = children.remove(&entry.entry_id).unwrap_or_default();
+        EntryTree {
+            entry,
+            children: nested
+                .into_iter()
+                .map(|child| grow(child, children))
+                .collect(),
+        }
+    }
+    roots
+        .into_iter()
+        .map(|root| grow(root, &mut children))
+        .collect()
 }
 
 async fn post_entry(
@@ -72,8 +187,38 @@ async fn post_entry(
     axum::extract::Json(entry): axum::extract::Json<Entry>,
 ) -> Result<Json<Entry>, AppError> {
     tracing::info!("Inserting new entry");
-    let new_entry_id = insert_entry(&db, &entry).await?;
-    select_entry(&db, new_entry_id).await
+    let mut tx = db.pool.begin().await?;
+    let new_entry_id = create_entry(&mut tx, &entry).await?;
+    tx.commit().await?;
+    select_entry(&db.pool, new_entry_id).await
+}
+
+/// Inserts an entry as the last child of its parent entry, or at the end of its block when it
+/// has none. Without a parent entry in the request, the entry goes to the end of its block,
+/// nested as deep as `nesting` asks and the entries above allow. Returns the new entry's id.
+pub(crate) async fn create_entry(
+    conn: &mut SqliteConnection,
+    entry: &Entry,
+) -> Result<i64, AppError> {
+    let new_entry_id = insert_entry(conn, entry).await?;
+    let parent_entry = match entry.parent_entry {
+        Some(parent_entry) => parent_entry,
+        None => select_parent_for_nesting(conn, entry.parent, entry.nesting, new_entry_id).await?,
+    };
+    let after = select_last_child(conn, entry.parent, parent_entry, new_entry_id).await?;
+    place_subtree(
+        conn,
+        new_entry_id,
+        Placement {
+            block: entry.parent,
+            parent_entry,
+            after,
+        },
+    )
+    .await?;
+    let tags = merge_text_tags(entry.tags.as_deref().unwrap_or_default(), None, &entry.text);
+    set_entry_tags(conn, new_entry_id, &tags).await?;
+    Ok(new_entry_id)
 }
 
 async fn put_entry(
@@ -86,27 +231,94 @@ async fn put_entry(
         return Err(AppError::BadRequest);
     }
     tracing::info!("Put entry: {:?}", entry_id);
+    let mut tx = db.pool.begin().await?;
+    update_entry(&mut tx, &entry).await?;
+    tx.commit().await?;
+
+    select_entry(&db.pool, entry.entry_id).await
+}
+
+/// Updates an entry. When its block or parent entry changes, the entry moves there together
+/// with its children, as the last child of the new parent entry. A request without a parent
+/// entry keeps the entry where it is, except that a changed `nesting` indents or outdents it
+/// one level at a time, as far as the entries around it allow.
+pub(crate) async fn update_entry(
+    conn: &mut SqliteConnection,
+    entry: &Entry,
+) -> Result<(), AppError> {
+    let old_text = select_entry_text(conn, entry.entry_id).await?;
+    let links = select_entry_links(conn, entry.entry_id).await?;
+    let (current_state, current_nesting) = sqlx::query_as::<_, (Option<TodoState>, i64)>(
+        "
+    SELECT todo_state, nesting FROM entries WHERE entry_id = ?1;
+        ",
+    )
+    .bind(entry.entry_id)
+    .fetch_one(&mut *conn)
+    .await?;
     sqlx::query(
         "
     UPDATE entries SET
-        parent=?2,
-        nesting=?3,
-        text=?4,
-        show_todo=?5,
-        is_done=?6
+        text=?2,
+        todo_state=?3,
+        due_date=CASE WHEN ?6 THEN ?4 ELSE due_date END,
+        scheduled_date=CASE WHEN ?7 THEN ?5 ELSE scheduled_date END,
+        updated_at=DATETIME('now')
     WHERE entry_id=?1;
             ",
     )
     .bind(entry.entry_id)
-    .bind(entry.parent)
-    .bind(entry.nesting)
-    .bind(entry.text)
-    .bind(entry.show_todo)
-    .bind(entry.is_done)
-    .execute(&db.pool)
+    .bind(&entry.text)
+    .bind(entry.resolve_todo_state(current_state))
+    .bind(entry.due_date.flatten())
+    .bind(entry.scheduled_date.flatten())
+    .bind(entry.due_date.is_some())
+    .bind(entry.scheduled_date.is_some())
+    .execute(&mut *conn)
     .await?;
-
-    select_entry(&db, entry.entry_id).await
+    let parent_entry = match entry.parent_entry {
+        Some(parent_entry) => Some(parent_entry),
+        None if entry.parent != links.0 => Some(None),
+        None => None,
+    };
+    match parent_entry {
+        Some(parent_entry) if links != (entry.parent, parent_entry) => {
+            let after = select_last_child(conn, entry.parent, parent_entry, entry.entry_id).await?;
+            place_subtree(
+                conn,
+                entry.entry_id,
+                Placement {
+                    block: entry.parent,
+                    parent_entry,
+                    after,
+                },
+            )
+            .await?;
+        }
+        Some(_) => {}
+        None => {
+            for _ in current_nesting..entry.nesting {
+                if !indent(conn, entry.entry_id).await? {
+                    break;
+                }
+            }
+            for _ in entry.nesting..current_nesting {
+                if !outdent(conn, entry.entry_id).await? {
+                    break;
+                }
+            }
+        }
+    }
+    let tags = match &entry.tags {
+        Some(tags) => tags.clone(),
+        None => select_entry(&mut *conn, entry.entry_id)
+            .await?
+            .0
+            .tags
+            .unwrap_or_default(),
+    };
+    let tags = merge_text_tags(&tags, Some(&old_text), &entry.text);
+    set_entry_tags(conn, entry.entry_id, &tags).await
 }
 
 async fn delete_entry_api(
@@ -115,67 +327,676 @@ async fn delete_entry_api(
     db: State<Arc<Database>>,
 ) -> impl IntoResponse {
     tracing::info!("Delete entry: {}", entry_id);
-    if sqlx::query(
+    let deleted = match db.pool.acquire().await {
+        Ok(mut conn) => delete_entry(&mut conn, entry_id).await,
+        Err(error) => Err(error.into()),
+    };
+    if deleted.is_ok() {
+        (StatusCode::NO_CONTENT, "Entry deleted")
+    } else {
+        (StatusCode::CONFLICT, "The entry could not be deleted")
+    }
+}
+
+/// Moves an entry and the entries nested under it to the trash. Returns whether there was an
+/// entry to delete.
+pub(crate) async fn delete_entry(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
         "
-        DELETE FROM entries WHERE entry_id = ?1;
+    WITH RECURSIVE subtree(entry_id) AS (
+        SELECT entry_id FROM entries WHERE entry_id = ?1 AND deleted_at IS NULL
+        UNION ALL
+        SELECT entries.entry_id FROM entries
+        JOIN subtree ON entries.parent_entry = subtree.entry_id
+        WHERE entries.deleted_at IS NULL
+    )
+    UPDATE entries SET deleted_at = DATETIME('now')
+    WHERE entry_id IN (SELECT entry_id FROM subtree);
+        ",
+    )
+    .bind(entry_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Places an entry, with the entries nested under it, right before or after another entry,
+/// making it a sibling of that entry.
+async fn move_entry(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+    axum::extract::Json(target): axum::extract::Json<MoveEntry>,
+) -> Result<Json<Entry>, AppError> {
+    tracing::info!("Move entry {}: {:?}", entry_id, target);
+    let mut tx = db.pool.begin().await?;
+    let sibling_id = match target {
+        MoveEntry::Before(sibling_id) | MoveEntry::After(sibling_id) => sibling_id,
+    };
+    let (block, parent_entry) = select_entry_links(&mut tx, sibling_id).await?;
+    let after = match target {
+        MoveEntry::Before(_) => select_sibling_before(&mut tx, sibling_id, entry_id).await?,
+        MoveEntry::After(_) => Some(sibling_id),
+    };
+    place_subtree(
+        &mut tx,
+        entry_id,
+        Placement {
+            block,
+            parent_entry,
+            after,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    select_entry(&db.pool, entry_id).await
+}
+
+/// Moves an entry to another block, possibly on another day. The children of the entry come
+/// along unless they are left behind, in which case they take the entry's place.
+async fn move_entry_to_block(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+    axum::extract::Json(target): axum::extract::Json<MoveToBlock>,
+) -> Result<Json<Entry>, AppError> {
+    tracing::info!("Move entry {} to block: {:?}", entry_id, target);
+    let mut tx = db.pool.begin().await?;
+    let (_, parent_entry) = select_entry_links(&mut tx, entry_id).await?;
+    if target.leave_children {
+        let children = sqlx::query_as::<_, (i64,)>(
+            "
+    UPDATE entries SET parent_entry = ?2 WHERE parent_entry = ?1
+    RETURNING entry_id;
             ",
+        )
+        .bind(entry_id)
+        .bind(parent_entry)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (child,) in children {
+            update_subtree_nesting(&mut tx, child).await?;
+        }
+    }
+    let top_level = sqlx::query_as::<_, (i64,)>(
+        "
+    SELECT entry_id FROM entries
+    WHERE parent = ?1 AND parent_entry IS NULL AND deleted_at IS NULL AND entry_id != ?2
+    ORDER BY position, entry_id;
+        ",
     )
+    .bind(target.block_id)
     .bind(entry_id)
-    .execute(&db.pool)
-    .await
-    .is_ok()
-    {
-        (StatusCode::NO_CONTENT, "Entry deleted")
-    } else {
-        (StatusCode::CONFLICT, "The entry could not be deleted")
+    .fetch_all(&mut *tx)
+    .await?;
+    let after = match target.position {
+        Some(0) => None,
+        Some(position) => top_level
+            .get(position - 1)
+            .or(top_level.last())
+            .map(|(id,)| *id),
+        None => top_level.last().map(|(id,)| *id),
+    };
+    place_subtree(
+        &mut tx,
+        entry_id,
+        Placement {
+            block: Some(target.block_id),
+            parent_entry: None,
+            after,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    select_entry(&db.pool, entry_id).await
+}
+
+/// Nests an entry, with its children, under the sibling above it.
+async fn indent_entry(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Entry>, AppError> {
+    tracing::info!("Indent entry: {}", entry_id);
+    let mut tx = db.pool.begin().await?;
+    if !indent(&mut tx, entry_id).await? {
+        return Err(AppError::BadRequest);
     }
+    tx.commit().await?;
+
+    select_entry(&db.pool, entry_id).await
 }
 
-async fn select_entry(db: &Database, entry_id: i64) -> Result<Json<Entry>, AppError> {
-    Ok(Json(
-        sqlx::query_as::<_, Entry>(
+/// Nests an entry under the sibling above it, returns false when there is no such sibling.
+async fn indent(conn: &mut SqliteConnection, entry_id: i64) -> Result<bool, AppError> {
+    let (block, _) = select_entry_links(conn, entry_id).await?;
+    let Some(new_parent) = select_sibling_before(conn, entry_id, entry_id).await? else {
+        return Ok(false);
+    };
+    let after = select_last_child(conn, block, Some(new_parent), entry_id).await?;
+    place_subtree(
+        conn,
+        entry_id,
+        Placement {
+            block,
+            parent_entry: Some(new_parent),
+            after,
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Moves an entry, with its children, out of its parent entry to right after it.
+async fn outdent_entry(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Entry>, AppError> {
+    tracing::info!("Outdent entry: {}", entry_id);
+    let mut tx = db.pool.begin().await?;
+    if !outdent(&mut tx, entry_id).await? {
+        return Err(AppError::BadRequest);
+    }
+    tx.commit().await?;
+
+    select_entry(&db.pool, entry_id).await
+}
+
+/// Moves an entry out of its parent entry to right after it, returns false when the entry is
+/// not nested.
+async fn outdent(conn: &mut SqliteConnection, entry_id: i64) -> Result<bool, AppError> {
+    let (block, parent_entry) = select_entry_links(conn, entry_id).await?;
+    let Some(parent_entry) = parent_entry else {
+        return Ok(false);
+    };
+    let (_, grandparent_entry) = select_entry_links(conn, parent_entry).await?;
+    place_subtree(
+        conn,
+        entry_id,
+        Placement {
+            block,
+            parent_entry: grandparent_entry,
+            after: Some(parent_entry),
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Copies an entry with the entries nested under it, placing the copy right after the
+/// original. Returns the copied entry.
+async fn duplicate_entry(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Entry>, AppError> {
+    tracing::info!("Duplicate entry: {}", entry_id);
+    let mut tx = db.pool.begin().await?;
+    let (block, parent_entry) = select_entry_links(&mut tx, entry_id).await?;
+    let mut copies: HashMap<i64, i64> = HashMap::new();
+    for original in select_subtree(&mut tx, entry_id).await? {
+        let copy_parent = if original == entry_id {
+            parent_entry
+        } else {
+            let (_, original_parent) = select_entry_links(&mut tx, original).await?;
+            original_parent.and_then(|parent| copies.get(&parent).copied())
+        };
+        let (copy,) = sqlx::query_as::<_, (i64,)>(
             "
+    INSERT INTO entries (
+        parent,
+        parent_entry,
+        nesting,
+        text,
+        todo_state,
+        due_date,
+        scheduled_date,
+        position,
+        updated_at
+    )
     SELECT
-        entry_id,
         parent,
+        ?2,
         nesting,
         text,
-        show_todo,
-        is_done
+        todo_state,
+        due_date,
+        scheduled_date,
+        position,
+        DATETIME('now')
+    FROM entries WHERE entry_id = ?1
+    RETURNING entry_id;
+            ",
+        )
+        .bind(original)
+        .bind(copy_parent)
+        .fetch_one(&mut *tx)
+        .await?;
+        copy_entry_tags(&mut tx, original, copy).await?;
+        copies.insert(original, copy);
+    }
+    let copy = copies[&entry_id];
+    place_subtree(
+        &mut tx,
+        copy,
+        Placement {
+            block,
+            parent_entry,
+            after: Some(entry_id),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    select_entry(&db.pool, copy).await
+}
+
+/// Where to put an entry with its children: in `block` under `parent_entry`, right after
+/// `after` and its children, or as the first child when `after` is `None`.
+struct Placement {
+    block: Option<i64>,
+    parent_entry: Option<i64>,
+    after: Option<i64>,
+}
+
+/// Moves an entry together with the entries nested under it. The entries of the block are
+/// numbered anew, so they keep consecutive positions with every entry followed by its children.
+async fn place_subtree(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+    placement: Placement,
+) -> Result<(), AppError> {
+    if let Some(block) = placement.block {
+        ensure_block_exists(conn, block).await?;
+    }
+    let subtree = select_subtree(conn, entry_id).await?;
+    for target in [placement.parent_entry, placement.after]
+        .into_iter()
+        .flatten()
+    {
+        if subtree.contains(&target) {
+            return Err(AppError::BadRequest);
+        }
+    }
+    if let Some(parent_entry) = placement.parent_entry {
+        if select_entry_links(conn, parent_entry).await?.0 != placement.block {
+            return Err(AppError::BadRequest);
+        }
+    }
+    let anchor = match placement.after {
+        Some(after) => {
+            if select_entry_links(conn, after).await? != (placement.block, placement.parent_entry) {
+                return Err(AppError::BadRequest);
+            }
+            select_subtree(conn, after)
+                .await?
+                .into_iter()
+                .rev()
+                .find(|id| !subtree.contains(id))
+        }
+        None => placement.parent_entry,
+    };
+    let mut order: Vec<i64> = sqlx::query_as::<_, (i64,)>(
+        "
+    SELECT entry_id FROM entries
+    WHERE parent IS ?1 AND deleted_at IS NULL
+    ORDER BY position, entry_id;
+        ",
+    )
+    .bind(placement.block)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|(id,)| id)
+    .filter(|id| !subtree.contains(id))
+    .collect();
+    let index = match anchor {
+        Some(anchor) => {
+            order
+                .iter()
+                .position(|id| *id == anchor)
+                .ok_or(AppError::NotFound)?
+                + 1
+        }
+        None => 0,
+    };
+    order.splice(index..index, subtree);
+
+    sqlx::query(
+        "
+    UPDATE entries SET parent_entry = ?2 WHERE entry_id = ?1;
+        ",
+    )
+    .bind(entry_id)
+    .bind(placement.parent_entry)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        "
+    UPDATE entries SET parent = ?1, position = ordered.key
+    FROM (SELECT key, value AS entry_id FROM json_each(?2)) AS ordered
+    WHERE entries.entry_id = ordered.entry_id;
+        ",
+    )
+    .bind(placement.block)
+    .bind(serde_json::to_string(&order).map_err(|_| AppError::InternalServer)?)
+    .execute(&mut *conn)
+    .await?;
+    update_subtree_nesting(conn, entry_id).await
+}
+
+/// Numbers the entries of a block from 0 in their current order, closing the gaps left by
+/// entries that moved to another block.
+pub(crate) async fn renumber_entries(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    UPDATE entries SET position = ordered.new_position
+    FROM (
+        SELECT entry_id, ROW_NUMBER() OVER (ORDER BY position, entry_id) - 1 AS new_position
+        FROM entries WHERE parent = ?1
+    ) AS ordered
+    WHERE entries.entry_id = ordered.entry_id;
+        ",
+    )
+    .bind(block_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Sets the nesting of an entry and the entries under it to their depth in the tree.
+pub(crate) async fn update_subtree_nesting(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    WITH RECURSIVE subtree(entry_id, nesting) AS (
+        SELECT
+            entry_id,
+            COALESCE((SELECT above.nesting + 1 FROM entries AS above
+                WHERE above.entry_id = entries.parent_entry), 0)
+        FROM entries WHERE entry_id = ?1
+        UNION ALL
+        SELECT entries.entry_id, subtree.nesting + 1 FROM entries
+        JOIN subtree ON entries.parent_entry = subtree.entry_id
+    )
+    UPDATE entries SET nesting = subtree.nesting
+    FROM subtree
+    WHERE entries.entry_id = subtree.entry_id;
+        ",
+    )
+    .bind(entry_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// The ids of an entry and of all entries nested under it, in the order of the block.
+async fn select_subtree(conn: &mut SqliteConnection, entry_id: i64) -> Result<Vec<i64>, AppError> {
+    let subtree = sqlx::query_as::<_, (i64,)>(
+        "
+    WITH RECURSIVE subtree(entry_id) AS (
+        SELECT entry_id FROM entries WHERE entry_id = ?1 AND deleted_at IS NULL
+        UNION ALL
+        SELECT entries.entry_id FROM entries
+        JOIN subtree ON entries.parent_entry = subtree.entry_id
+        WHERE entries.deleted_at IS NULL
+    )
+    SELECT entries.entry_id FROM entries
+    JOIN subtree ON entries.entry_id = subtree.entry_id
+    ORDER BY entries.position, entries.entry_id;
+        ",
+    )
+    .bind(entry_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    if subtree.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    Ok(subtree.into_iter().map(|(id,)| id).collect())
+}
+
+/// The block and the parent entry of an entry.
+async fn select_entry_links(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+) -> Result<(Option<i64>, Option<i64>), AppError> {
+    Ok(sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+        "
+    SELECT parent, parent_entry FROM entries WHERE entry_id = ?1 AND deleted_at IS NULL;
+        ",
+    )
+    .bind(entry_id)
+    .fetch_one(&mut *conn)
+    .await?)
+}
+
+/// The sibling right above an entry, not counting the entry `excluded`.
+async fn select_sibling_before(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+    excluded: i64,
+) -> Result<Option<i64>, AppError> {
+    Ok(sqlx::query_as::<_, (i64,)>(
+        "
+    SELECT sibling.entry_id FROM entries AS sibling
+    JOIN entries AS entry ON entry.entry_id = ?1
+    WHERE sibling.parent IS entry.parent AND sibling.parent_entry IS entry.parent_entry
+    AND sibling.deleted_at IS NULL AND sibling.entry_id != ?2
+    AND (sibling.position < entry.position
+        OR (sibling.position = entry.position AND sibling.entry_id < entry.entry_id))
+    ORDER BY sibling.position DESC, sibling.entry_id DESC
+    LIMIT 1;
+        ",
+    )
+    .bind(entry_id)
+    .bind(excluded)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|(id,)| id))
+}
+
+/// The parent entry for an entry added at the end of `block` with the given `nesting`: the
+/// deepest entry above it, from the last entry of the block up, that is less nested. Not
+/// counting the entry `excluded`.
+async fn select_parent_for_nesting(
+    conn: &mut SqliteConnection,
+    block: Option<i64>,
+    nesting: i64,
+    excluded: i64,
+) -> Result<Option<i64>, AppError> {
+    Ok(sqlx::query_as::<_, (i64,)>(
+        "
+    WITH RECURSIVE above(entry_id, parent_entry, nesting) AS (
+        SELECT * FROM (
+            SELECT entry_id, parent_entry, nesting FROM entries
+            WHERE parent IS ?1 AND deleted_at IS NULL AND entry_id != ?3
+            ORDER BY position DESC, entry_id DESC
+            LIMIT 1
+        )
+        UNION ALL
+        SELECT entries.entry_id, entries.parent_entry, entries.nesting FROM entries
+        JOIN above ON entries.entry_id = above.parent_entry
+    )
+    SELECT entry_id FROM above
+    WHERE nesting < ?2
+    ORDER BY nesting DESC
+    LIMIT 1;
+        ",
+    )
+    .bind(block)
+    .bind(nesting)
+    .bind(excluded)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|(id,)| id))
+}
+
+/// The last entry directly under `parent_entry` in `block`, not counting the entry `excluded`.
+async fn select_last_child(
+    conn: &mut SqliteConnection,
+    block: Option<i64>,
+    parent_entry: Option<i64>,
+    excluded: i64,
+) -> Result<Option<i64>, AppError> {
+    Ok(sqlx::query_as::<_, (i64,)>(
+        "
+    SELECT entry_id FROM entries
+    WHERE parent IS ?1 AND parent_entry IS ?2 AND deleted_at IS NULL AND entry_id != ?3
+    ORDER BY position DESC, entry_id DESC
+    LIMIT 1;
+        ",
+    )
+    .bind(block)
+    .bind(parent_entry)
+    .bind(excluded)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|(id,)| id))
+}
+
+pub(crate) async fn select_entry<'c>(
+    executor: impl SqliteExecutor<'c>,
+    entry_id: i64,
+) -> Result<Json<Entry>, AppError> {
+    Ok(Json(
+        sqlx::query_as::<_, Entry>(
+            "
+    SELECT
+        entries.entry_id,
+        entries.parent,
+        entries.parent_entry,
+        entries.nesting,
+        entries.text,
+        entries.todo_state,
+        entries.closed_at,
+        entries.position,
+        entries.due_date,
+        entries.scheduled_date,
+        entries.migrated_from,
+        COALESCE(GROUP_CONCAT(DISTINCT tags.name), '') AS tags
     FROM entries
-    WHERE entries.entry_id = ?1;
+
+    LEFT JOIN tagged_entries ON entries.entry_id = tagged_entries.entry_fk
+    LEFT JOIN tags ON tagged_entries.tag_fk = tags.tag_id
+    WHERE entries.entry_id = ?1 AND entries.deleted_at IS NULL
+    GROUP BY entries.entry_id;
         ",
         )
         .bind(entry_id)
-        .fetch_one(&db.pool)
+        .fetch_one(executor)
         .await?,
     ))
 }
 
-async fn insert_entry(db: &Database, entry: &Entry) -> Result<i64, AppError> {
+/// Inserts an entry at the end of its block, `create_entry` then puts it in its place.
+async fn insert_entry(conn: &mut SqliteConnection, entry: &Entry) -> Result<i64, AppError> {
     let new_entry_id = sqlx::query_as::<_, InsertResult>(
         "
     INSERT INTO entries (
         parent,
         nesting,
         text,
-        show_todo,
-        is_done
+        todo_state,
+        due_date,
+        scheduled_date,
+        position,
+        updated_at
     ) VALUES (
         ?1,
+        0,
         ?2,
         ?3,
         ?4,
-        ?5
+        ?5,
+        (SELECT COALESCE(MAX(position) + 1, 0) FROM entries WHERE parent IS ?1),
+        DATETIME('now')
     ) RETURNING entry_id AS id;
         ",
     )
     .bind(entry.parent)
-    .bind(entry.nesting)
     .bind(&entry.text)
-    .bind(entry.show_todo)
-    .bind(entry.is_done)
-    .fetch_one(&db.pool)
+    .bind(entry.resolve_todo_state(None))
+    .bind(entry.due_date.flatten())
+    .bind(entry.scheduled_date.flatten())
+    .fetch_one(&mut *conn)
     .await?;
     Ok(new_entry_id.id)
 }
+
+async fn select_entry_text(conn: &mut SqliteConnection, entry_id: i64) -> Result<String, AppError> {
+    let (text,) = sqlx::query_as::<_, (String,)>(
+        "
+    SELECT text FROM entries WHERE entry_id = ?1 AND deleted_at IS NULL;
+        ",
+    )
+    .bind(entry_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_id: i64, parent_entry: Option<i64>) -> Entry {
+        serde_json::from_value(serde_json::json!({
+            "entry_id": entry_id,
+            "parent": 1,
+            "parent_entry": parent_entry,
+            "nesting": 0,
+            "text": "",
+        }))
+        .unwrap()
+    }
+
+    /// The trees written like `1(2 3(4)) 5`.
+    fn shape(trees: &[EntryTree]) -> String {
+        trees
+            .iter()
+            .map(|tree| match tree.children.as_slice() {
+                [] => tree.entry.entry_id.to_string(),
+                children => format!("{}({})", tree.entry.entry_id, shape(children)),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn nests_entries_in_order() {
+        let trees = build_trees(vec![
+            entry(1, None),
+            entry(2, Some(1)),
+            entry(3, Some(1)),
+            entry(4, Some(3)),
+            entry(5, None),
+        ]);
+        assert_eq!(shape(&trees), "1(2 3(4)) 5");
+    }
+
+    #[test]
+    fn entries_with_a_missing_parent_become_roots() {
+        let trees = build_trees(vec![
+            entry(2, Some(1)),
+            entry(3, Some(2)),
+            entry(4, Some(9)),
+        ]);
+        assert_eq!(shape(&trees), "2(3) 4");
+    }
+
+    #[test]
+    fn no_entries_no_trees() {
+        assert!(build_trees(Vec::new()).is_empty());
+    }
+}
@@ -1,9 +1,11 @@
+use std::cmp::Ordering;
+
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde_json::{json, Value};
 use sqlx::migrate::MigrateError;
 
 #[derive(Debug)]
@@ -14,11 +16,25 @@ pub enum AppError {
     WrongCredentials,
     InternalServer,
     MissingCredentials,
+    InvalidTimeRange,
+    OverlappingBlocks(Vec<i64>),
+    BlocksNotAdjacent(Vec<i64>),
+    /// The filter query could not be parsed, `position` counts characters from its start.
+    InvalidQuery {
+        position: usize,
+        message: String,
+    },
+    /// The operation at `index` of a batch of `operations` failed with `error`.
+    BatchFailed {
+        index: usize,
+        operations: usize,
+        error: Box<AppError>,
+    },
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+impl AppError {
+    fn status_and_message(&self) -> (StatusCode, &'static str) {
+        match self {
             AppError::BadRequest => (StatusCode::BAD_REQUEST, "Bad request"),
             AppError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token"),
             AppError::MissingCredentials => (StatusCode::BAD_REQUEST, "Missing credentials"),
@@ -27,12 +43,66 @@ impl IntoResponse for AppError {
             AppError::InternalServer => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
             }
-        };
+            AppError::InvalidTimeRange => (StatusCode::BAD_REQUEST, "End is before start"),
+            AppError::OverlappingBlocks(_) => {
+                (StatusCode::CONFLICT, "Block overlaps with existing blocks")
+            }
+            AppError::BlocksNotAdjacent(_) => (StatusCode::CONFLICT, "Blocks are not adjacent"),
+            AppError::InvalidQuery { .. } => (StatusCode::BAD_REQUEST, "Invalid query"),
+            AppError::BatchFailed { error, .. } => {
+                (error.status_and_message().0, "Batch was rolled back")
+            }
+        }
+    }
+
+    fn body(&self) -> Value {
+        let (_, error_message) = self.status_and_message();
+        match self {
+            AppError::OverlappingBlocks(block_ids) | AppError::BlocksNotAdjacent(block_ids) => {
+                json!({
+                    "error": error_message,
+                    "block_ids": block_ids,
+                })
+            }
+            AppError::InvalidQuery { position, message } => json!({
+                "error": error_message,
+                "message": message,
+                "position": position,
+            }),
+            AppError::BatchFailed {
+                index,
+                operations,
+                error,
+            } => {
+                let report: Vec<Value> = (0..*operations)
+                    .map(|i| match i.cmp(index) {
+                        Ordering::Less => json!({ "status": "rolled_back" }),
+                        Ordering::Equal => {
+                            let mut failed = error.body();
+                            failed["status"] = json!("failed");
+                            failed
+                        }
+                        Ordering::Greater => json!({ "status": "skipped" }),
+                    })
+                    .collect();
+                json!({
+                    "error": error_message,
+                    "index": index,
+                    "operations": report,
+                })
+            }
+            _ => json!({
+                "error": error_message,
+            }),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = self.status_and_message();
         tracing::warn!("{} {}", status, error_message);
-        let body = Json(json!({
-            "error": error_message,
-        }));
-        (status, body).into_response()
+        (status, Json(self.body())).into_response()
     }
 }
 
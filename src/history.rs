@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::SqliteConnection;
+
+use crate::{
+    auth::Claims,
+    blocks::{resolve_overlaps, BlockSpan},
+    database::Database,
+    entries::update_subtree_nesting,
+    errors::AppError,
+    models::{HistoryEntry, OverlapMode, RecordType},
+};
+
+pub fn history_router() -> Router<Arc<Database>> {
+    Router::new()
+        .route("/{record_type}/{record_id}", get(get_history))
+        .route("/{history_id}/revert", post(revert_to_version))
+}
+
+async fn get_history(
+    _: Claims,
+    Path((record_type, record_id)): Path<(RecordType, i64)>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<HistoryEntry>>, AppError> {
+    tracing::info!("Get history of {}: {}", record_type.as_str(), record_id);
+    Ok(Json(
+        sqlx::query_as::<_, HistoryEntry>(
+            "
+    SELECT
+        history_id,
+        record_type,
+        record_id,
+        action,
+        old_value,
+        new_value,
+        changed_at
+    FROM history
+    WHERE record_type = ?1 AND record_id = ?2
+    ORDER BY history_id;
+        ",
+        )
+        .bind(record_type.as_str())
+        .bind(record_id)
+        .fetch_all(&db.pool)
+        .await?,
+    ))
+}
+
+/// Restores a record as it was after the given change, or before it for a delete. Columns
+/// missing from older snapshots keep their current value.
+async fn revert_to_version(
+    _: Claims,
+    Path(history_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<HistoryEntry>, AppError> {
+    tracing::info!("Revert to version: {}", history_id);
+    let mut tx = db.pool.begin().await?;
+    let version = select_history_entry(&mut tx, history_id).await?;
+    let snapshot = version
+        .new_value
+        .or(version.old_value)
+        .ok_or(AppError::NotFound)?
+        .to_string();
+    let record_type: RecordType =
+        serde_json::from_value(serde_json::Value::String(version.record_type))
+            .map_err(|_| AppError::InternalServer)?;
+    match record_type {
+        RecordType::Block => revert_block(&mut tx, version.record_id, &snapshot).await?,
+        RecordType::Entry => revert_entry(&mut tx, version.record_id, &snapshot).await?,
+        RecordType::Project => revert_project(&mut tx, version.record_id, &snapshot).await?,
+    }
+    let latest = sqlx::query_as::<_, (i64,)>(
+        "
+    SELECT MAX(history_id) FROM history WHERE record_type = ?1 AND record_id = ?2;
+        ",
+    )
+    .bind(record_type.as_str())
+    .bind(version.record_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let latest = select_history_entry(&mut tx, latest.0).await?;
+    tx.commit().await?;
+
+    Ok(Json(latest))
+}
+
+async fn select_history_entry(
+    conn: &mut SqliteConnection,
+    history_id: i64,
+) -> Result<HistoryEntry, AppError> {
+    Ok(sqlx::query_as::<_, HistoryEntry>(
+        "
+    SELECT
+        history_id,
+        record_type,
+        record_id,
+        action,
+        old_value,
+        new_value,
+        changed_at
+    FROM history
+    WHERE history_id = ?1;
+        ",
+    )
+    .bind(history_id)
+    .fetch_one(&mut *conn)
+    .await?)
+}
+
+fn snapshot_timestamp(snapshot: &serde_json::Value, key: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(snapshot.get(key)?.as_str()?, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|timestamp| timestamp.and_utc())
+}
+
+async fn revert_block(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+    snapshot: &str,
+) -> Result<(), AppError> {
+    let values: serde_json::Value =
+        serde_json::from_str(snapshot).map_err(|_| AppError::InternalServer)?;
+    if values
+        .get("deleted_at")
+        .is_none_or(|deleted| deleted.is_null())
+    {
+        let span = BlockSpan {
+            block_id: Some(block_id),
+            start: snapshot_timestamp(&values, "start").ok_or(AppError::InternalServer)?,
+            end: snapshot_timestamp(&values, "end"),
+        };
+        resolve_overlaps(conn, &span, None, OverlapMode::Reject).await?;
+    }
+    sqlx::query(
+        "
+    INSERT INTO blocks (
+        block_id,
+        text,
+        project,
+        start,
+        end,
+        duration,
+        estimate,
+        auto_closed,
+        deleted_at
+    ) VALUES (
+        ?1,
+        json_extract(?2, '$.text'),
+        json_extract(?2, '$.project'),
+        json_extract(?2, '$.start'),
+        json_extract(?2, '$.end'),
+        0,
+        json_extract(?2, '$.estimate'),
+        COALESCE(json_extract(?2, '$.auto_closed'), FALSE),
+        json_extract(?2, '$.deleted_at')
+    )
+    ON CONFLICT (block_id) DO UPDATE SET
+        text = CASE WHEN json_type(?2, '$.text') IS NULL THEN text ELSE excluded.text END,
+        project = CASE WHEN json_type(?2, '$.project') IS NULL THEN project ELSE excluded.project END,
+        start = CASE WHEN json_type(?2, '$.start') IS NULL THEN start ELSE excluded.start END,
+        end = CASE WHEN json_type(?2, '$.end') IS NULL THEN end ELSE excluded.end END,
+        estimate = CASE WHEN json_type(?2, '$.estimate') IS NULL THEN estimate ELSE excluded.estimate END,
+        auto_closed = CASE WHEN json_type(?2, '$.auto_closed') IS NULL THEN auto_closed ELSE excluded.auto_closed END,
+        deleted_at = CASE WHEN json_type(?2, '$.deleted_at') IS NULL THEN deleted_at ELSE excluded.deleted_at END;
+        ",
+    )
+    .bind(block_id)
+    .bind(snapshot)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn revert_entry(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+    snapshot: &str,
+) -> Result<(), AppError> {
+    // Snapshots from before the todo states have their todo flags mapped onto a state.
+    sqlx::query(
+        "
+    INSERT INTO entries (
+        entry_id,
+        parent,
+        parent_entry,
+        nesting,
+        text,
+        todo_state,
+        due_date,
+        scheduled_date,
+        migrated_from,
+        updated_at,
+        deleted_at
+    ) VALUES (
+        ?1,
+        json_extract(?2, '$.parent'),
+        (SELECT entry_id FROM entries WHERE entry_id = json_extract(?2, '$.parent_entry')),
+        json_extract(?2, '$.nesting'),
+        json_extract(?2, '$.text'),
+        COALESCE(json_extract(?2, '$.todo_state'), CASE WHEN json_extract(?2, '$.show_todo') THEN
+            CASE WHEN json_extract(?2, '$.is_done') THEN 'done' ELSE 'open' END
+        END),
+        json_extract(?2, '$.due_date'),
+        json_extract(?2, '$.scheduled_date'),
+        (SELECT entry_id FROM entries WHERE entry_id = json_extract(?2, '$.migrated_from')),
+        DATETIME('now'),
+        json_extract(?2, '$.deleted_at')
+    )
+    ON CONFLICT (entry_id) DO UPDATE SET
+        parent = CASE WHEN json_type(?2, '$.parent') IS NULL THEN parent ELSE excluded.parent END,
+        parent_entry = CASE WHEN json_type(?2, '$.parent_entry') IS NULL THEN parent_entry ELSE excluded.parent_entry END,
+        nesting = CASE WHEN json_type(?2, '$.nesting') IS NULL THEN nesting ELSE excluded.nesting END,
+        text = CASE WHEN json_type(?2, '$.text') IS NULL THEN text ELSE excluded.text END,
+        todo_state = CASE
+            WHEN json_type(?2, '$.todo_state') IS NULL AND json_type(?2, '$.show_todo') IS NULL THEN todo_state
+            ELSE excluded.todo_state
+        END,
+        due_date = CASE WHEN json_type(?2, '$.due_date') IS NULL THEN due_date ELSE excluded.due_date END,
+        scheduled_date = CASE WHEN json_type(?2, '$.scheduled_date') IS NULL THEN scheduled_date ELSE excluded.scheduled_date END,
+        migrated_from = CASE WHEN json_type(?2, '$.migrated_from') IS NULL THEN migrated_from ELSE excluded.migrated_from END,
+        updated_at = excluded.updated_at,
+        deleted_at = CASE WHEN json_type(?2, '$.deleted_at') IS NULL THEN deleted_at ELSE excluded.deleted_at END;
+        ",
+    )
+    .bind(entry_id)
+    .bind(snapshot)
+    .execute(&mut *conn)
+    .await?;
+    update_subtree_nesting(conn, entry_id).await
+}
+
+async fn revert_project(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    snapshot: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    INSERT INTO projects (
+        project_id,
+        name,
+        archived,
+        color,
+        estimate,
+        rounding_mode,
+        rounding_interval
+    ) VALUES (
+        ?1,
+        json_extract(?2, '$.name'),
+        COALESCE(json_extract(?2, '$.archived'), FALSE),
+        json_extract(?2, '$.color'),
+        json_extract(?2, '$.estimate'),
+        json_extract(?2, '$.rounding_mode'),
+        json_extract(?2, '$.rounding_interval')
+    )
+    ON CONFLICT (project_id) DO UPDATE SET
+        name = CASE WHEN json_type(?2, '$.name') IS NULL THEN name ELSE excluded.name END,
+        archived = CASE WHEN json_type(?2, '$.archived') IS NULL THEN archived ELSE excluded.archived END,
+        color = CASE WHEN json_type(?2, '$.color') IS NULL THEN color ELSE excluded.color END,
+        estimate = CASE WHEN json_type(?2, '$.estimate') IS NULL THEN estimate ELSE excluded.estimate END,
+        rounding_mode = CASE WHEN json_type(?2, '$.rounding_mode') IS NULL THEN rounding_mode ELSE excluded.rounding_mode END,
+        rounding_interval = CASE WHEN json_type(?2, '$.rounding_interval') IS NULL THEN rounding_interval ELSE excluded.rounding_interval END;
+        ",
+    )
+    .bind(project_id)
+    .bind(snapshot)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    auth::Claims,
+    blocks::select_blocks_in_range,
+    database::Database,
+    entries::select_entries_in_range,
+    errors::AppError,
+    models::{day_end, day_start, Direction, JournalPage, JournalParams, PageUnit},
+    query::Filter,
+    settings::Timezone,
+};
+
+const DEFAULT_COUNT: u32 = 3;
+const MAX_COUNT: u32 = 100;
+
+pub fn journal_router() -> Router<Arc<Database>> {
+    Router::new().route("/", get(get_journal_page))
+}
+
+async fn get_journal_page(
+    _: Claims,
+    Timezone(tz): Timezone,
+    params: Query<JournalParams>,
+    db: State<Arc<Database>>,
+) -> Result<Json<JournalPage>, AppError> {
+    let cursor = params.cursor.unwrap_or_else(Utc::now);
+    let count = params.count.unwrap_or(DEFAULT_COUNT).clamp(1, MAX_COUNT);
+    tracing::info!(
+        "Getting journal page of {} {:?} {:?}: {:?}",
+        count,
+        params.unit,
+        params.direction,
+        cursor
+    );
+    let (start, end) = match params.unit {
+        PageUnit::Blocks => block_page_range(&db, cursor, params.direction, count).await?,
+        PageUnit::Days => day_page_range(&db, tz, cursor, params.direction, count).await?,
+    };
+
+    let range = (Some(start.naive_utc()), Some(end.naive_utc()));
+    let blocks = select_blocks_in_range(&db, range.0, range.1, &Filter::default()).await?;
+    let entries = select_entries_in_range(&db, range.0, range.1, &Filter::default()).await?;
+    let (has_more_before, has_more_after) = sqlx::query_as::<_, (bool, bool)>(
+        "
+    SELECT
+        EXISTS(SELECT 1 FROM blocks WHERE start < DATETIME(?1) AND deleted_at IS NULL),
+        EXISTS(SELECT 1 FROM blocks WHERE start >= DATETIME(?2) AND deleted_at IS NULL);
+        ",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_one(&db.pool)
+    .await?;
+
+    Ok(Json(JournalPage {
+        start,
+        end,
+        blocks,
+        entries,
+        has_more_before,
+        has_more_after,
+    }))
+}
+
+/// The range covering `count` blocks before or after the cursor.
+async fn block_page_range(
+    db: &Database,
+    cursor: DateTime<Utc>,
+    direction: Direction,
+    count: u32,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), AppError> {
+    let starts = sqlx::query_as::<_, (DateTime<Utc>,)>(
+        "
+    SELECT start FROM blocks
+    WHERE CASE WHEN ?2 THEN start >= DATETIME(?1) ELSE start < DATETIME(?1) END
+    AND deleted_at IS NULL
+    ORDER BY
+        CASE WHEN ?2 THEN start END ASC,
+        CASE WHEN ?2 THEN NULL ELSE start END DESC
+    LIMIT ?3;
+        ",
+    )
+    .bind(cursor)
+    .bind(direction == Direction::After)
+    .bind(count)
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(match (direction, starts.last()) {
+        (_, None) => (cursor, cursor),
+        (Direction::Before, Some((oldest,))) => (*oldest, cursor),
+        (Direction::After, Some((newest,))) => (cursor, *newest + chrono::Duration::seconds(1)),
+    })
+}
+
+/// The range covering `count` local calendar days with blocks before or after the cursor.
+async fn day_page_range(
+    db: &Database,
+    tz: Tz,
+    cursor: DateTime<Utc>,
+    direction: Direction,
+    count: u32,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), AppError> {
+    let mut edge = cursor;
+    for _ in 0..count {
+        let nearest = sqlx::query_as::<_, (DateTime<Utc>,)>(
+            "
+    SELECT start FROM blocks
+    WHERE CASE WHEN ?2 THEN start >= DATETIME(?1) ELSE start < DATETIME(?1) END
+    AND deleted_at IS NULL
+    ORDER BY
+        CASE WHEN ?2 THEN start END ASC,
+        CASE WHEN ?2 THEN NULL ELSE start END DESC
+    LIMIT 1;
+            ",
+        )
+        .bind(edge)
+        .bind(direction == Direction::After)
+        .fetch_optional(&db.pool)
+        .await?;
+        let Some((start,)) = nearest else {
+            break;
+        };
+        edge = match direction {
+            Direction::Before => day_start(tz, start),
+            Direction::After => day_end(tz, start),
+        };
+    }
+
+    Ok(match direction {
+        Direction::Before => (edge, cursor),
+        Direction::After => (cursor, edge),
+    })
+}
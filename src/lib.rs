@@ -1,8 +1,19 @@
 pub mod auth;
+pub mod auto_close;
+pub mod batch;
 pub mod blocks;
 pub mod colors;
 pub mod database;
 pub mod entries;
 pub mod errors;
+pub mod history;
+pub mod journal;
 pub mod models;
 pub mod projects;
+pub mod query;
+pub mod reports;
+pub mod search;
+pub mod settings;
+pub mod tags;
+pub mod todos;
+pub mod trash;
@@ -8,8 +8,11 @@ use tracing::info_span;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use appendable_proto::{
-    auth::auth_router, blocks::blocks_router, colors::colors_router, entries::entries_router,
-    projects::projects_router,
+    auth::auth_router, auto_close, batch::batch_router, blocks::blocks_router,
+    colors::colors_router, entries::entries_router, history::history_router,
+    journal::journal_router, projects::projects_router, reports::reports_router,
+    search::search_router, settings::settings_router, tags::tags_router, todos::todos_router,
+    trash,
 };
 
 use appendable_proto::database::Database;
@@ -30,11 +33,22 @@ async fn main() {
         .init();
 
     let state = Arc::new(Database::new().await.unwrap());
+    tokio::spawn(auto_close::run(state.clone()));
+    tokio::spawn(trash::run(state.clone()));
 
     let app = Router::new()
         .nest("/api/blocks", blocks_router())
         .nest("/api/entries", entries_router())
+        .nest("/api/journal", journal_router())
+        .nest("/api/history", history_router())
+        .nest("/api/batch", batch_router())
         .nest("/api/projects", projects_router())
+        .nest("/api/tags", tags_router())
+        .nest("/api/todos", todos_router())
+        .nest("/api/reports", reports_router())
+        .nest("/api/search", search_router())
+        .nest("/api/settings", settings_router())
+        .nest("/api/trash", trash::trash_router())
         .nest("/api/colors", colors_router())
         .with_state(state)
         .nest("/api/auth", auth_router())
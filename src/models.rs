@@ -1,7 +1,10 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::{sqlite::SqliteRow, FromRow, Row};
 
+use crate::{errors::AppError, query::Filter};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Block {
     pub block_id: i64,
@@ -10,7 +13,18 @@ pub struct Block {
     pub project_name: Option<String>,
     pub start: DateTime<Utc>,
     pub end: Option<DateTime<Utc>>,
+    /// Tracked time in seconds, without the pauses.
     pub duration: i64,
+    /// Tracked time in seconds, including the pauses.
+    #[serde(default)]
+    pub gross_duration: i64,
+    /// Estimated duration in seconds, kept when a request leaves it out.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub estimate: Option<Option<i64>>,
+    /// Set when the block was closed automatically because it ran for too long, kept when a
+    /// request leaves it out.
+    #[serde(default)]
+    pub auto_closed: Option<bool>,
     pub tags: Vec<String>,
 }
 
@@ -23,12 +37,10 @@ impl<'r> FromRow<'r, SqliteRow> for Block {
         let start = row.try_get("start")?;
         let end = row.try_get("end")?;
         let duration = row.try_get("duration")?;
-        let tags: Vec<String> = row
-            .try_get::<&str, &str>("tags")?
-            .split(",")
-            .filter(|s| !s.is_empty())
-            .map(str::to_string)
-            .collect();
+        let gross_duration = row.try_get("gross_duration")?;
+        let estimate = Some(row.try_get("estimate")?);
+        let auto_closed = Some(row.try_get("auto_closed")?);
+        let tags = try_get_tags(row)?;
         Ok(Block {
             block_id,
             text,
@@ -37,27 +49,290 @@ impl<'r> FromRow<'r, SqliteRow> for Block {
             start,
             end,
             duration,
+            gross_duration,
+            estimate,
+            auto_closed,
             tags,
         })
     }
 }
 
-#[derive(FromRow, Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Entry {
     pub entry_id: i64,
+    /// The block the entry belongs to.
     pub parent: Option<i64>,
+    /// The entry this entry is nested under, `Some(None)` for entries at the top of their
+    /// block. When a request leaves it out, the entry is placed by `nesting` instead.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub parent_entry: Option<Option<i64>>,
+    /// Depth of the entry in the tree of its block, follows from `parent_entry`.
     pub nesting: i64,
     pub text: String,
+    /// State of the todo, `None` when the entry is no todo.
+    #[serde(default)]
+    pub todo_state: Option<TodoState>,
+    /// Whether the entry is a todo. Only decides the todo state when `todo_state` is missing.
+    #[serde(default)]
     pub show_todo: bool,
+    /// Whether the todo is done. Only decides the todo state when `todo_state` is missing.
+    #[serde(default)]
     pub is_done: bool,
+    /// When the todo was done or cancelled.
+    #[serde(default)]
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Place of the entry among the entries of its block, counting from 0.
+    #[serde(default)]
+    pub position: i64,
+    /// Day a todo has to be done by, kept when a request leaves it out.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub due_date: Option<Option<NaiveDate>>,
+    /// Day a todo is planned to be worked on, kept when a request leaves it out.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub scheduled_date: Option<Option<NaiveDate>>,
+    /// The entry this todo was migrated from.
+    #[serde(default)]
+    pub migrated_from: Option<i64>,
+    /// Left out of a request, the entry keeps its tags apart from hashtags gone from the text.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl Entry {
+    /// The todo state to store when `current` is stored now. Without a `todo_state` the state
+    /// follows `show_todo` and `is_done`, keeping the current state while it matches them.
+    pub fn resolve_todo_state(&self, current: Option<TodoState>) -> Option<TodoState> {
+        if self.todo_state.is_some() {
+            return self.todo_state;
+        }
+        if TodoState::flags(current) == (self.show_todo, self.is_done) {
+            return current;
+        }
+        match (self.show_todo, self.is_done) {
+            (false, _) => None,
+            (true, true) => Some(TodoState::Done),
+            (true, false) => Some(TodoState::Open),
+        }
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Entry {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let entry_id = row.try_get("entry_id")?;
+        let parent = row.try_get("parent")?;
+        let parent_entry = Some(row.try_get("parent_entry")?);
+        let nesting = row.try_get("nesting")?;
+        let text = row.try_get("text")?;
+        let todo_state = row.try_get("todo_state")?;
+        let (show_todo, is_done) = TodoState::flags(todo_state);
+        let closed_at = row.try_get("closed_at")?;
+        let position = row.try_get("position")?;
+        let due_date = Some(row.try_get("due_date")?);
+        let scheduled_date = Some(row.try_get("scheduled_date")?);
+        let migrated_from = row.try_get("migrated_from")?;
+        let tags = Some(try_get_tags(row)?);
+        Ok(Entry {
+            entry_id,
+            parent,
+            parent_entry,
+            nesting,
+            text,
+            todo_state,
+            show_todo,
+            is_done,
+            closed_at,
+            position,
+            due_date,
+            scheduled_date,
+            migrated_from,
+            tags,
+        })
+    }
+}
+
+/// Wraps a field that is present in `Some`, also when it is `null`. Together with
+/// `#[serde(default)]` an `Option<Option<T>>` then tells a missing field from a `null` one.
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum TodoState {
+    Open,
+    InProgress,
+    Blocked,
+    Done,
+    Cancelled,
+    /// Carried over to a later block, see `Entry::migrated_from` on the copy.
+    Migrated,
+}
+
+impl TodoState {
+    /// The `show_todo` and `is_done` flags matching a todo state. Clients that only know the
+    /// flags see every closed todo as done, so cancelled and migrated todos do not show up as
+    /// open ones.
+    pub fn flags(state: Option<TodoState>) -> (bool, bool) {
+        (state.is_some(), state.is_some_and(TodoState::is_closed))
+    }
+
+    /// Whether the todo needs no more work.
+    pub fn is_closed(self) -> bool {
+        matches!(
+            self,
+            TodoState::Done | TodoState::Cancelled | TodoState::Migrated
+        )
+    }
+}
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct TodoTransition {
+    pub transition_id: i64,
+    pub entry_id: i64,
+    pub from_state: Option<TodoState>,
+    pub to_state: Option<TodoState>,
+    pub changed_at: DateTime<Utc>,
+    /// Made up for todos that existed before their transitions were recorded, `changed_at`
+    /// is only a guess then.
+    pub backfilled: bool,
+}
+
+/// An entry together with the entries nested under it.
+#[derive(Serialize, Debug)]
+pub struct EntryTree {
+    #[serde(flatten)]
+    pub entry: Entry,
+    pub children: Vec<EntryTree>,
+}
+
+/// An open todo with the block it was written in.
+#[derive(Serialize, Debug)]
+pub struct Todo {
+    #[serde(flatten)]
+    pub entry: Entry,
+    pub block_text: String,
+    pub block_start: DateTime<Utc>,
+    pub project: Option<i64>,
+    pub project_name: Option<String>,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Todo {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Todo {
+            entry: Entry::from_row(row)?,
+            block_text: row.try_get("block_text")?,
+            block_start: row.try_get("block_start")?,
+            project: row.try_get("project")?,
+            project_name: row.try_get("project_name")?,
+        })
+    }
+}
+
+/// Reads the comma separated `tags` column produced by `GROUP_CONCAT` into a list of tag names.
+fn try_get_tags(row: &SqliteRow) -> Result<Vec<String>, sqlx::Error> {
+    Ok(row
+        .try_get::<&str, &str>("tags")?
+        .split(",")
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect())
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct Pause {
+    pub pause_id: i64,
+    pub block_id: i64,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Project {
     pub project_id: i64,
     pub name: String,
     pub archived: bool,
     pub color: Option<i64>,
+    /// Estimated duration in seconds for all blocks of the project together, kept when a
+    /// request leaves it out.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub estimate: Option<Option<i64>>,
+    /// Rounding of the durations of the project's blocks, the global rounding when `null`.
+    /// Kept when a request leaves it out.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub rounding: Option<Option<Rounding>>,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Project {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let mode: Option<String> = row.try_get("rounding_mode")?;
+        let interval: Option<u32> = row.try_get("rounding_interval")?;
+        let rounding = match mode {
+            Some(mode) => Some(Rounding {
+                mode: serde_json::from_value(serde_json::Value::String(mode)).map_err(|error| {
+                    sqlx::Error::ColumnDecode {
+                        index: "rounding_mode".to_string(),
+                        source: Box::new(error),
+                    }
+                })?,
+                interval: interval.unwrap_or_default(),
+            }),
+            None => None,
+        };
+        Ok(Project {
+            project_id: row.try_get("project_id")?,
+            name: row.try_get("name")?,
+            archived: row.try_get("archived")?,
+            color: row.try_get("color")?,
+            estimate: Some(row.try_get("estimate")?),
+            rounding: Some(rounding),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingMode {
+    /// Durations are used as tracked.
+    #[default]
+    None,
+    /// Durations are rounded to the nearest multiple of the interval.
+    Nearest,
+    /// Durations are rounded up to the next multiple of the interval.
+    Up,
+}
+
+impl RoundingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundingMode::None => "none",
+            RoundingMode::Nearest => "nearest",
+            RoundingMode::Up => "up",
+        }
+    }
+}
+
+/// How durations are rounded in reports, e.g. up to the next 15 minutes. Only the reported
+/// durations are rounded, the tracked timestamps stay as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Rounding {
+    pub mode: RoundingMode,
+    /// Interval in minutes to round to.
+    pub interval: u32,
+}
+
+impl Default for Rounding {
+    fn default() -> Self {
+        Self {
+            mode: RoundingMode::None,
+            interval: 15,
+        }
+    }
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
@@ -73,6 +348,240 @@ pub struct Tag {
     pub archived: bool,
 }
 
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapMode {
+    /// Refuse writes that overlap other blocks.
+    #[default]
+    Reject,
+    /// Shorten the overlapping neighbours so the written block fits.
+    Trim,
+}
+
+/// Where to move an entry, e.g. `{"before": 12}` places it right before entry 12.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveEntry {
+    Before(i64),
+    After(i64),
+}
+
+/// Moves an entry to the top level of another block.
+#[derive(Deserialize, Debug)]
+pub struct MoveToBlock {
+    pub block_id: i64,
+    /// Place among the top level entries of the block, at the end when missing.
+    #[serde(default)]
+    pub position: Option<usize>,
+    /// Leaves the children of the entry behind, in its old place.
+    #[serde(default)]
+    pub leave_children: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OverlapParams {
+    #[serde(default)]
+    pub overlap: OverlapMode,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MergeBlocks {
+    pub block_ids: Vec<i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SplitBlock {
+    pub at: DateTime<Utc>,
+    /// Entries that move to the second half, all other entries stay with the first half.
+    #[serde(default)]
+    pub second_half_entries: Vec<i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StopBlock {
+    pub end: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PauseBlock {
+    pub at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TagName {
+    pub name: String,
+}
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct BlockEstimate {
+    pub block_id: i64,
+    pub text: String,
+    pub project: Option<i64>,
+    pub project_name: Option<String>,
+    pub start: DateTime<Utc>,
+    pub estimate: Option<i64>,
+    /// Duration in seconds after rounding.
+    pub duration: i64,
+    /// Duration in seconds as tracked.
+    pub raw_duration: i64,
+    /// Duration minus estimate, positive when the block took longer than estimated.
+    pub difference: Option<i64>,
+}
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct ProjectEstimate {
+    pub project_id: i64,
+    pub name: String,
+    pub estimate: Option<i64>,
+    /// Sum of the estimates of the blocks in the range.
+    pub block_estimate: i64,
+    /// Rounded duration of the blocks in the range.
+    pub duration: i64,
+    /// Rounded duration of all blocks of the project, to compare with the project estimate.
+    pub total_duration: i64,
+    /// Total duration minus the project estimate.
+    pub difference: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EstimateReport {
+    pub blocks: Vec<BlockEstimate>,
+    pub projects: Vec<ProjectEstimate>,
+}
+
+#[derive(Deserialize, Serialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum RecordType {
+    Block,
+    Entry,
+    Project,
+}
+
+impl RecordType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::Block => "block",
+            RecordType::Entry => "entry",
+            RecordType::Project => "project",
+        }
+    }
+}
+
+/// A single change in a batch, e.g. `{"op": "update", "type": "entry", "data": {...}}` or
+/// `{"op": "delete", "type": "block", "id": 4}`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperation {
+    Create(BatchRecord),
+    Update(BatchRecord),
+    Delete {
+        #[serde(rename = "type")]
+        record_type: RecordType,
+        id: i64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+pub enum BatchRecord {
+    Block(Block),
+    Entry(Entry),
+    Project(Project),
+}
+
+/// Outcome of a batch operation: the record as saved, or the id of the deleted record.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum BatchResult {
+    Saved(BatchRecord),
+    Deleted {
+        #[serde(rename = "type")]
+        record_type: RecordType,
+        id: i64,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistoryEntry {
+    pub history_id: i64,
+    pub record_type: String,
+    pub record_id: i64,
+    /// Either `insert`, `update` or `delete`.
+    pub action: String,
+    pub old_value: Option<serde_json::Value>,
+    pub new_value: Option<serde_json::Value>,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for HistoryEntry {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let parse_json = |column: &str| -> Result<Option<serde_json::Value>, sqlx::Error> {
+            row.try_get::<Option<&str>, &str>(column)?
+                .map(serde_json::from_str)
+                .transpose()
+                .map_err(|error| sqlx::Error::ColumnDecode {
+                    index: column.to_string(),
+                    source: Box::new(error),
+                })
+        };
+        Ok(HistoryEntry {
+            history_id: row.try_get("history_id")?,
+            record_type: row.try_get("record_type")?,
+            record_id: row.try_get("record_id")?,
+            action: row.try_get("action")?,
+            old_value: parse_json("old_value")?,
+            new_value: parse_json("new_value")?,
+            changed_at: row.try_get("changed_at")?,
+        })
+    }
+}
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct TrashedBlock {
+    pub block_id: i64,
+    pub text: String,
+    pub project: Option<i64>,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct TrashedEntry {
+    pub entry_id: i64,
+    pub parent: Option<i64>,
+    pub text: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Trash {
+    pub blocks: Vec<TrashedBlock>,
+    pub entries: Vec<TrashedEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// IANA name of the timezone that decides where days start and end.
+    pub timezone: Tz,
+    /// Days after which deleted blocks and entries are removed from the trash for good.
+    pub trash_retention_days: u32,
+    /// Rounding of durations in reports for projects without their own rounding.
+    pub rounding: Rounding,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            timezone: Tz::UTC,
+            trash_retention_days: 30,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
 #[derive(FromRow, Serialize, Deserialize, Debug)]
 pub struct InsertResult {
     pub id: i64,
@@ -83,6 +592,50 @@ pub struct NextDataResponse {
     pub block_timestamp: DateTime<Utc>,
 }
 
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Towards older blocks.
+    #[default]
+    Before,
+    /// Towards newer blocks.
+    After,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PageUnit {
+    /// Page by days that contain blocks, empty days are skipped.
+    #[default]
+    Days,
+    /// Page by a number of blocks.
+    Blocks,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct JournalParams {
+    /// Where the page starts, defaults to now. Paging before excludes the cursor itself,
+    /// paging after includes it.
+    pub cursor: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default)]
+    pub unit: PageUnit,
+    pub count: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JournalPage {
+    /// Inclusive start of the page, use it as cursor to page further before.
+    pub start: DateTime<Utc>,
+    /// Exclusive end of the page, use it as cursor to page further after.
+    pub end: DateTime<Utc>,
+    pub blocks: Vec<Block>,
+    pub entries: Vec<Entry>,
+    pub has_more_before: bool,
+    pub has_more_after: bool,
+}
+
 #[derive(Deserialize)]
 pub struct RangeParams {
     start: Option<DateTime<Utc>>,
@@ -90,29 +643,261 @@ pub struct RangeParams {
 }
 
 impl RangeParams {
-    pub fn get_start(&self) -> NaiveDateTime {
-        self.start.unwrap_or(day_start()).naive_utc()
+    /// The start of the range, defaults to the start of today in the given timezone.
+    pub fn get_start(&self, tz: Tz) -> NaiveDateTime {
+        self.start
+            .unwrap_or_else(|| day_start(tz, Utc::now()))
+            .naive_utc()
+    }
+
+    /// The end of the range, defaults to the end of today in the given timezone.
+    pub fn get_end(&self, tz: Tz) -> NaiveDateTime {
+        self.end
+            .unwrap_or_else(|| day_end(tz, Utc::now()))
+            .naive_utc()
     }
 
-    pub fn get_end(&self) -> NaiveDateTime {
-        self.end.unwrap_or(day_end()).naive_utc()
+    /// The range to select when filtering with `filter`. Without a filter this is the range
+    /// from `get_start` to `get_end`. With one the range is only bounded by the explicitly
+    /// given start and end and the days of the filter's `after` and `before`.
+    pub fn get_filtered_range(
+        &self,
+        tz: Tz,
+        filter: &Filter,
+    ) -> (Option<NaiveDateTime>, Option<NaiveDateTime>) {
+        if filter.is_empty() {
+            return (Some(self.get_start(tz)), Some(self.get_end(tz)));
+        }
+        let after = filter.after.map(|day| local_midnight(tz, day));
+        let before = filter.before.map(|day| local_midnight(tz, day));
+        let start = self.start.into_iter().chain(after).max();
+        let end = self.end.into_iter().chain(before).min();
+        (
+            start.map(|start| start.naive_utc()),
+            end.map(|end| end.naive_utc()),
+        )
     }
 }
 
-fn day_start() -> DateTime<Utc> {
-    let now = Utc::now();
-    now.date_naive()
-        .and_hms_opt(0, 0, 0)
-        .unwrap()
-        .and_local_timezone(Utc)
-        .unwrap()
+#[derive(Deserialize, Debug)]
+pub struct FilterParams {
+    q: Option<String>,
 }
 
-fn day_end() -> DateTime<Utc> {
-    let now = Utc::now();
-    now.date_naive()
-        .and_hms_opt(23, 59, 59)
-        .unwrap()
-        .and_local_timezone(Utc)
+impl FilterParams {
+    /// The parsed `q` parameter, an empty filter when it is not given.
+    pub fn get_filter(&self) -> Result<Filter, AppError> {
+        self.q.as_deref().unwrap_or_default().parse()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchParams {
+    /// Words to search for, each word matches words starting with it.
+    pub q: String,
+    pub project: Option<i64>,
+    /// Only blocks starting from this moment.
+    pub start: Option<DateTime<Utc>>,
+    /// Only blocks starting before this moment.
+    pub end: Option<DateTime<Utc>>,
+    pub limit: Option<u32>,
+}
+
+impl SearchParams {
+    /// The search words as an FTS5 query of prefix terms that all have to match. Quoting every
+    /// word keeps the FTS5 query syntax out of reach of the user.
+    pub fn get_match_query(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .q
+            .split_whitespace()
+            .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(terms.join(" "))
+    }
+}
+
+/// A block or entry matching a search, with the block it belongs to.
+#[derive(FromRow, Serialize, Debug)]
+pub struct SearchHit {
+    pub record_type: RecordType,
+    pub record_id: i64,
+    /// Part of the text around the match as HTML, the matching words wrapped in `<mark>` tags
+    /// and everything else escaped.
+    pub snippet: String,
+    /// Relevance among the hits of the same record type, lower is more relevant.
+    pub rank: f64,
+    pub block_id: i64,
+    pub block_text: String,
+    pub block_start: DateTime<Utc>,
+    pub project: Option<i64>,
+    pub project_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TodoParams {
+    /// Lists the todos in this state instead of the ones still to be done.
+    pub state: Option<TodoState>,
+    pub project: Option<i64>,
+    /// First day of the due window.
+    pub due_from: Option<NaiveDate>,
+    /// Last day of the due window, included in the window.
+    pub due_until: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct TagParams {
+    tags: Option<String>,
+}
+
+impl TagParams {
+    /// The tag names in the comma separated `tags` parameter.
+    pub fn get_tags(&self) -> Vec<String> {
+        self.tags
+            .as_deref()
+            .unwrap_or_default()
+            .split(",")
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// The comma separated `tags` parameter as a JSON array, for use with `json_each`.
+    /// Returns `None` when no tags are given, meaning no filtering should happen.
+    pub fn get_tags_json(&self) -> Option<String> {
+        let tags = self.get_tags();
+        if tags.is_empty() {
+            return None;
+        }
+        serde_json::to_string(&tags).ok()
+    }
+}
+
+/// The start of the local calendar day that contains `at`.
+pub fn day_start(tz: Tz, at: DateTime<Utc>) -> DateTime<Utc> {
+    local_midnight(tz, at.with_timezone(&tz).date_naive())
+}
+
+/// The end of the local calendar day that contains `at`, which is the start of the next day
+/// since ranges do not include their end.
+pub fn day_end(tz: Tz, at: DateTime<Utc>) -> DateTime<Utc> {
+    let day = at.with_timezone(&tz).date_naive();
+    local_midnight(tz, day.succ_opt().unwrap_or(day))
+}
+
+/// The moment a local calendar day starts. When a DST change skips midnight, the day starts at
+/// the first local time that exists.
+pub fn local_midnight(tz: Tz, day: NaiveDate) -> DateTime<Utc> {
+    (0..=3)
+        .filter_map(|hour| day.and_hms_opt(hour, 0, 0))
+        .find_map(|time| tz.from_local_datetime(&time).earliest())
+        .map(|midnight| midnight.with_timezone(&Utc))
+        .unwrap_or_else(|| day.and_time(NaiveTime::MIN).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{America::Santiago, Europe::Berlin, UTC};
+
+    use super::*;
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn utc(month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, month, day, hour, 0, 0).unwrap()
+    }
+
+    fn flags_entry(show_todo: bool, is_done: bool) -> Entry {
+        serde_json::from_value(serde_json::json!({
+            "entry_id": 1,
+            "parent": 1,
+            "nesting": 0,
+            "text": "",
+            "show_todo": show_todo,
+            "is_done": is_done,
+        }))
         .unwrap()
+    }
+
+    #[test]
+    fn explicit_todo_state_wins() {
+        let mut entry = flags_entry(false, false);
+        entry.todo_state = Some(TodoState::Blocked);
+        assert_eq!(
+            entry.resolve_todo_state(Some(TodoState::Done)),
+            Some(TodoState::Blocked)
+        );
+    }
+
+    #[test]
+    fn flags_matching_the_current_state_keep_it() {
+        let closed = flags_entry(true, true);
+        for state in [TodoState::Done, TodoState::Cancelled, TodoState::Migrated] {
+            assert_eq!(closed.resolve_todo_state(Some(state)), Some(state));
+        }
+        let open = flags_entry(true, false);
+        for state in [TodoState::Open, TodoState::InProgress, TodoState::Blocked] {
+            assert_eq!(open.resolve_todo_state(Some(state)), Some(state));
+        }
+        assert_eq!(flags_entry(false, false).resolve_todo_state(None), None);
+    }
+
+    #[test]
+    fn changed_flags_pick_a_plain_state() {
+        assert_eq!(
+            flags_entry(true, true).resolve_todo_state(Some(TodoState::InProgress)),
+            Some(TodoState::Done)
+        );
+        assert_eq!(
+            flags_entry(true, false).resolve_todo_state(Some(TodoState::Cancelled)),
+            Some(TodoState::Open)
+        );
+        assert_eq!(
+            flags_entry(true, false).resolve_todo_state(None),
+            Some(TodoState::Open)
+        );
+        assert_eq!(
+            flags_entry(false, true).resolve_todo_state(Some(TodoState::Done)),
+            None
+        );
+    }
+
+    #[test]
+    fn midnight_in_the_local_timezone() {
+        assert_eq!(local_midnight(UTC, day(8, 4)), utc(8, 4, 0));
+        assert_eq!(local_midnight(Berlin, day(8, 4)), utc(8, 3, 22));
+        assert_eq!(local_midnight(Berlin, day(1, 4)), utc(1, 3, 23));
+    }
+
+    #[test]
+    fn day_starting_a_dst_change_keeps_its_midnight() {
+        // Berlin skips from 02:00 to 03:00 on 2024-03-31, midnight still exists.
+        assert_eq!(local_midnight(Berlin, day(3, 31)), utc(3, 30, 23));
+    }
+
+    #[test]
+    fn skipped_midnight_starts_the_day_at_the_first_local_time() {
+        // Santiago skips from 00:00 to 01:00 on 2024-09-08, 01:00 -03 is 04:00 UTC.
+        assert_eq!(local_midnight(Santiago, day(9, 8)), utc(9, 8, 4));
+    }
+
+    #[test]
+    fn day_start_and_end_enclose_the_local_day() {
+        // 23:30 UTC is already the next day in Berlin.
+        let at = Utc.with_ymd_and_hms(2024, 8, 4, 23, 30, 0).unwrap();
+        assert_eq!(day_start(Berlin, at), utc(8, 4, 22));
+        assert_eq!(day_end(Berlin, at), utc(8, 5, 22));
+    }
+
+    #[test]
+    fn day_with_skipped_midnight_is_shorter() {
+        let at = utc(9, 8, 12);
+        assert_eq!(day_start(Santiago, at), utc(9, 8, 4));
+        assert_eq!(day_end(Santiago, at), utc(9, 9, 3));
+    }
 }
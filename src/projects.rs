@@ -1,11 +1,11 @@
 use std::sync::Arc;
 
-use anyhow::Result;
 use axum::{
     extract::State,
     routing::{get, put},
     Json, Router,
 };
+use sqlx::{SqliteConnection, SqliteExecutor};
 
 use crate::{
     auth::Claims,
@@ -28,7 +28,10 @@ async fn get_projects(_: Claims, db: State<Arc<Database>>) -> Result<Json<Vec<Pr
             project_id,
             name,
             archived,
-            color
+            color,
+            estimate,
+            rounding_mode,
+            rounding_interval
         FROM projects;
             ",
         )
@@ -43,11 +46,15 @@ async fn post_project(
     axum::extract::Json(project): axum::extract::Json<Project>,
 ) -> Result<Json<Project>, AppError> {
     tracing::info!("Post new project: {:?}", project);
-    let new_project_id = insert_project(&db, &project).await?;
-    Ok(select_project(&db, new_project_id).await?)
+    let mut conn = db.pool.acquire().await?;
+    let new_project_id = insert_project(&mut conn, &project).await?;
+    select_project(&mut *conn, new_project_id).await
 }
 
-async fn select_project(db: &Database, project_id: i64) -> Result<Json<Project>, AppError> {
+pub(crate) async fn select_project<'c>(
+    executor: impl SqliteExecutor<'c>,
+    project_id: i64,
+) -> Result<Json<Project>, AppError> {
     Ok(Json(
         sqlx::query_as::<_, Project>(
             "
@@ -55,34 +62,50 @@ async fn select_project(db: &Database, project_id: i64) -> Result<Json<Project>,
         project_id,
         name,
         archived,
-        color
+        color,
+        estimate,
+        rounding_mode,
+        rounding_interval
     FROM projects WHERE project_id = ?1;
         ",
         )
         .bind(project_id)
-        .fetch_one(&db.pool)
+        .fetch_one(executor)
         .await?,
     ))
 }
 
-async fn insert_project(db: &Database, project: &Project) -> Result<i64> {
+pub(crate) async fn insert_project(
+    conn: &mut SqliteConnection,
+    project: &Project,
+) -> Result<i64, AppError> {
+    let rounding = project.rounding.flatten();
     let new_project_id = sqlx::query_as::<_, InsertResult>(
         "
     INSERT INTO projects (
         name,
         archived,
-        color
+        color,
+        estimate,
+        rounding_mode,
+        rounding_interval
     ) VALUES (
         ?1,
         ?2,
-        ?3
+        ?3,
+        ?4,
+        ?5,
+        ?6
     ) RETURNING project_id AS id;
         ",
     )
     .bind(&project.name)
     .bind(project.archived)
-    .bind(&project.color)
-    .fetch_one(&db.pool)
+    .bind(project.color)
+    .bind(project.estimate.flatten())
+    .bind(rounding.map(|rounding| rounding.mode.as_str()))
+    .bind(rounding.map(|rounding| rounding.interval))
+    .fetch_one(&mut *conn)
     .await?;
     Ok(new_project_id.id)
 }
@@ -92,21 +115,131 @@ async fn put_project(
     db: State<Arc<Database>>,
     axum::extract::Json(project): axum::extract::Json<Project>,
 ) -> Result<Json<Project>, AppError> {
+    let mut conn = db.pool.acquire().await?;
+    update_project(&mut conn, &project).await?;
+
+    select_project(&mut *conn, project.project_id).await
+}
+
+pub(crate) async fn update_project(
+    conn: &mut SqliteConnection,
+    project: &Project,
+) -> Result<(), AppError> {
+    let rounding = project.rounding.flatten();
     sqlx::query(
         "
     UPDATE projects SET
         name=?2,
         archived=?3,
-        color=?4
+        color=?4,
+        estimate=CASE WHEN ?8 THEN ?5 ELSE estimate END,
+        rounding_mode=CASE WHEN ?9 THEN ?6 ELSE rounding_mode END,
+        rounding_interval=CASE WHEN ?9 THEN ?7 ELSE rounding_interval END
     WHERE project_id=?1;
         ",
     )
     .bind(project.project_id)
-    .bind(project.name)
+    .bind(&project.name)
     .bind(project.archived)
     .bind(project.color)
-    .execute(&db.pool)
+    .bind(project.estimate.flatten())
+    .bind(rounding.map(|rounding| rounding.mode.as_str()))
+    .bind(rounding.map(|rounding| rounding.interval))
+    .bind(project.estimate.is_some())
+    .bind(project.rounding.is_some())
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Deletes a project, detaching its blocks. Returns whether there was a project to delete.
+pub(crate) async fn delete_project(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+) -> Result<bool, AppError> {
+    sqlx::query(
+        "
+    UPDATE blocks SET project = NULL WHERE project = ?1;
+        ",
+    )
+    .bind(project_id)
+    .execute(&mut *conn)
     .await?;
+    let result = sqlx::query(
+        "
+    DELETE FROM projects WHERE project_id = ?1;
+        ",
+    )
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::models::{Rounding, RoundingMode};
+
+    #[sqlx::test]
+    async fn update_without_rounding_keeps_it(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let project: Project = serde_json::from_value(serde_json::json!({
+            "project_id": 0,
+            "name": "Review",
+            "archived": false,
+            "color": null,
+            "rounding": {"mode": "up", "interval": 30},
+        }))
+        .unwrap();
+        let project_id = insert_project(&mut conn, &project).await.unwrap();
+
+        let update: Project = serde_json::from_value(serde_json::json!({
+            "project_id": project_id,
+            "name": "Code review",
+            "archived": false,
+            "color": null,
+        }))
+        .unwrap();
+        update_project(&mut conn, &update).await.unwrap();
+
+        let Json(project) = select_project(&mut *conn, project_id).await.unwrap();
+        assert_eq!(project.name, "Code review");
+        assert_eq!(
+            project.rounding,
+            Some(Some(Rounding {
+                mode: RoundingMode::Up,
+                interval: 30
+            }))
+        );
+    }
+
+    #[sqlx::test]
+    async fn update_with_null_rounding_clears_it(pool: SqlitePool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let project: Project = serde_json::from_value(serde_json::json!({
+            "project_id": 0,
+            "name": "Review",
+            "archived": false,
+            "color": null,
+            "rounding": {"mode": "nearest", "interval": 15},
+        }))
+        .unwrap();
+        let project_id = insert_project(&mut conn, &project).await.unwrap();
+
+        let update: Project = serde_json::from_value(serde_json::json!({
+            "project_id": project_id,
+            "name": "Review",
+            "archived": false,
+            "color": null,
+            "rounding": null,
+        }))
+        .unwrap();
+        update_project(&mut conn, &update).await.unwrap();
 
-    select_project(&db, project.project_id).await
+        let Json(project) = select_project(&mut *conn, project_id).await.unwrap();
+        assert_eq!(project.rounding, Some(None));
+    }
 }
@@ -0,0 +1,355 @@
+//! The filter syntax accepted by the `q` parameter when getting blocks and entries, such as
+//! `project:Review tag:urgent after:2025-08-01 todo:open "timing"`.
+//!
+//! A term is either `key:value` or a bare word or `"quoted text"` that has to appear in the
+//! text. Values may be quoted as well, e.g. `project:"Code review"`. Terms with different keys
+//! all have to match, terms with the same key match when any of them does, except for text,
+//! where every term has to appear.
+
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{errors::AppError, models::TodoState};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Filter {
+    /// Project names, matched without regard to case.
+    pub projects: Vec<String>,
+    /// Tag names including the leading `#`.
+    pub tags: Vec<String>,
+    pub todo_states: Vec<TodoState>,
+    /// Texts that all have to appear, matched without regard to case.
+    pub texts: Vec<String>,
+    /// Only blocks starting on or after this local day.
+    pub after: Option<NaiveDate>,
+    /// Only blocks starting before this local day.
+    pub before: Option<NaiveDate>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        *self == Filter::default()
+    }
+
+    /// The project names as a JSON array for use with `json_each`, `None` when not filtering.
+    pub fn get_projects_json(&self) -> Option<String> {
+        to_json_array(&self.projects)
+    }
+
+    pub fn get_tags_json(&self) -> Option<String> {
+        to_json_array(&self.tags)
+    }
+
+    pub fn get_todo_states_json(&self) -> Option<String> {
+        to_json_array(&self.todo_states)
+    }
+
+    pub fn get_texts_json(&self) -> Option<String> {
+        to_json_array(&self.texts)
+    }
+
+    fn add(
+        &mut self,
+        key: &str,
+        value: String,
+        key_position: usize,
+        position: usize,
+    ) -> Result<(), AppError> {
+        match key {
+            "project" => self.projects.push(value),
+            "tag" if value.starts_with('#') => self.tags.push(value),
+            "tag" => self.tags.push(format!("#{value}")),
+            "todo" => self.todo_states.push(parse_todo_state(&value, position)?),
+            "after" => self.after = Some(parse_date(&value, position)?),
+            "before" => self.before = Some(parse_date(&value, position)?),
+            _ => {
+                return Err(invalid(
+                    key_position,
+                    format!(
+                        "Unknown key '{key}', expected one of project, tag, todo, after or before"
+                    ),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_json_array<T: Serialize>(values: &[T]) -> Option<String> {
+    if values.is_empty() {
+        return None;
+    }
+    serde_json::to_string(values).ok()
+}
+
+impl FromStr for Filter {
+    type Err = AppError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: query.chars().collect(),
+            position: 0,
+        };
+        let mut filter = Filter::default();
+        while let Some(term) = parser.next_term()? {
+            match term {
+                Term::Text(text) => filter.texts.push(text),
+                Term::Keyed {
+                    key,
+                    value,
+                    key_position,
+                    value_position,
+                } => filter.add(&key, value, key_position, value_position)?,
+            }
+        }
+        Ok(filter)
+    }
+}
+
+fn parse_date(value: &str, position: usize) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        invalid(
+            position,
+            format!("Invalid date '{value}', expected a date like 2025-08-01"),
+        )
+    })
+}
+
+fn parse_todo_state(value: &str, position: usize) -> Result<TodoState, AppError> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase())).map_err(|_| {
+        invalid(
+            position,
+            format!(
+                "Unknown todo state '{value}', expected one of open, in_progress, blocked, \
+                 done, cancelled or migrated"
+            ),
+        )
+    })
+}
+
+fn invalid(position: usize, message: String) -> AppError {
+    AppError::InvalidQuery { position, message }
+}
+
+enum Term {
+    Text(String),
+    Keyed {
+        key: String,
+        value: String,
+        /// Where the key and the value start, for reporting errors about them.
+        key_position: usize,
+        value_position: usize,
+    },
+}
+
+/// Splits a query into terms. Positions count characters from the start of the query.
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next_term(&mut self) -> Result<Option<Term>, AppError> {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+        let start = self.position;
+        match self.peek() {
+            None => Ok(None),
+            Some('"') => Ok(Some(Term::Text(self.quoted()?))),
+            Some(_) => {
+                let word = self.word(|c| c == ':' || c == '"');
+                if self.peek() != Some(':') {
+                    return match self.peek() {
+                        Some('"') => Err(invalid(
+                            self.position,
+                            "Unexpected quote inside a word".to_string(),
+                        )),
+                        _ => Ok(Some(Term::Text(word))),
+                    };
+                }
+                self.position += 1;
+                let position = self.position;
+                let value = match self.peek() {
+                    Some('"') => self.quoted()?,
+                    _ => self.word(|c| c == '"'),
+                };
+                if self.peek() == Some('"') {
+                    return Err(invalid(
+                        self.position,
+                        "Unexpected quote inside a word".to_string(),
+                    ));
+                }
+                if word.is_empty() {
+                    return Err(invalid(start, "Missing key before ':'".to_string()));
+                }
+                if value.is_empty() {
+                    return Err(invalid(position, format!("Missing value after '{word}:'")));
+                }
+                Ok(Some(Term::Keyed {
+                    key: word.to_lowercase(),
+                    value,
+                    key_position: start,
+                    value_position: position,
+                }))
+            }
+        }
+    }
+
+    /// Reads up to the next whitespace or a character for which `stop` holds.
+    fn word(&mut self, stop: impl Fn(char) -> bool) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && !stop(c)) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    /// Reads a quoted text, a quote inside it is written as `\"`.
+    fn quoted(&mut self) -> Result<String, AppError> {
+        let start = self.position;
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(invalid(start, "Missing closing quote".to_string()));
+                }
+                Some('"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some('\\') if self.chars.get(self.position + 1) == Some(&'"') => {
+                    text.push('"');
+                    self.position += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+        if text.trim().is_empty() {
+            return Err(invalid(start, "Empty quoted text".to_string()));
+        }
+        if self.peek().is_some_and(|c| !c.is_whitespace()) {
+            return Err(invalid(
+                self.position,
+                "Expected a space after the closing quote".to_string(),
+            ));
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Filter {
+        query.parse().unwrap()
+    }
+
+    /// The position and message of the error for a query that should not parse.
+    fn parse_error(query: &str) -> (usize, String) {
+        match query.parse::<Filter>() {
+            Err(AppError::InvalidQuery { position, message }) => (position, message),
+            other => panic!("expected an invalid query for {query:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_keyed_terms_and_text() {
+        let filter = parse(r#"project:Review tag:urgent after:2025-08-01 todo:open "timing""#);
+        assert_eq!(filter.projects, vec!["Review"]);
+        assert_eq!(filter.tags, vec!["#urgent"]);
+        assert_eq!(filter.after, NaiveDate::from_ymd_opt(2025, 8, 1));
+        assert_eq!(filter.todo_states, vec![TodoState::Open]);
+        assert_eq!(filter.texts, vec!["timing"]);
+    }
+
+    #[test]
+    fn empty_query_is_empty_filter() {
+        assert!(parse("").is_empty());
+        assert!(parse("   ").is_empty());
+    }
+
+    #[test]
+    fn keys_and_states_ignore_case() {
+        let filter = parse("TODO:In_Progress Project:x");
+        assert_eq!(filter.todo_states, vec![TodoState::InProgress]);
+        assert_eq!(filter.projects, vec!["x"]);
+    }
+
+    #[test]
+    fn tags_keep_a_leading_hash() {
+        assert_eq!(parse("tag:#work tag:home").tags, vec!["#work", "#home"]);
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces_and_colons() {
+        let filter = parse(r#"project:"Code review" "a: b""#);
+        assert_eq!(filter.projects, vec!["Code review"]);
+        assert_eq!(filter.texts, vec!["a: b"]);
+    }
+
+    #[test]
+    fn escaped_quotes_inside_quotes() {
+        let filter = parse(r#""say \"hi\"" project:"the \"best\"""#);
+        assert_eq!(filter.texts, vec![r#"say "hi""#]);
+        assert_eq!(filter.projects, vec![r#"the "best""#]);
+    }
+
+    #[test]
+    fn positions_count_characters() {
+        assert_eq!(parse_error("äöü foo:bar").0, 4);
+    }
+
+    #[test]
+    fn unknown_key_points_at_the_key() {
+        let (position, message) = parse_error("timing foo:bar");
+        assert_eq!(position, 7);
+        assert!(message.contains("'foo'"));
+    }
+
+    #[test]
+    fn bad_date_points_at_the_value() {
+        let (position, message) = parse_error("after:2025-13-01");
+        assert_eq!(position, 6);
+        assert!(message.contains("'2025-13-01'"));
+        assert_eq!(parse_error("x before:yesterday").0, 9);
+    }
+
+    #[test]
+    fn unknown_todo_state() {
+        assert_eq!(parse_error("todo:maybe").0, 5);
+    }
+
+    #[test]
+    fn missing_closing_quote_points_at_the_opening_quote() {
+        assert_eq!(parse_error(r#"a "open"#).0, 2);
+        assert_eq!(parse_error(r#"project:"open"#).0, 8);
+        assert_eq!(parse_error(r#""ends in \""#).0, 0);
+    }
+
+    #[test]
+    fn missing_key_or_value() {
+        assert_eq!(parse_error(":x").0, 0);
+        assert_eq!(parse_error("tag: x").0, 4);
+        assert_eq!(parse_error("tag:").0, 4);
+    }
+
+    #[test]
+    fn stray_quotes() {
+        assert_eq!(parse_error(r#"a"b"#).0, 1);
+        assert_eq!(parse_error(r#"tag:a"b""#).0, 5);
+        assert_eq!(parse_error(r#""a"b"#).0, 3);
+        assert_eq!(parse_error(r#""  ""#).0, 0);
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use crate::{
+    auth::Claims,
+    database::Database,
+    errors::AppError,
+    models::{BlockEstimate, EstimateReport, ProjectEstimate, RangeParams, Todo},
+    settings::{load_settings, Timezone},
+};
+
+/// The blocks that are not deleted, with their duration rounded by the rounding of their
+/// project, or else by the global rounding bound to `?3` (mode) and `?4` (interval in minutes).
+const ROUNDED_BLOCKS: &str = "
+    policies AS (
+        SELECT
+            blocks.block_id,
+            COALESCE(projects.rounding_mode, ?3) AS mode,
+            60 * CASE
+                WHEN projects.rounding_mode IS NULL THEN ?4
+                ELSE COALESCE(projects.rounding_interval, 0)
+            END AS interval
+        FROM blocks
+        LEFT JOIN projects ON blocks.project = projects.project_id
+    ), rounded_blocks AS (
+        SELECT
+            blocks.block_id,
+            blocks.project,
+            blocks.start,
+            blocks.estimate,
+            CASE
+                WHEN policies.interval <= 0 THEN blocks.duration
+                WHEN policies.mode = 'nearest'
+                    THEN (blocks.duration + policies.interval / 2) / policies.interval * policies.interval
+                WHEN policies.mode = 'up'
+                    THEN (blocks.duration + policies.interval - 1) / policies.interval * policies.interval
+                ELSE blocks.duration
+            END AS duration
+        FROM blocks
+        JOIN policies ON policies.block_id = blocks.block_id
+        WHERE blocks.deleted_at IS NULL
+    )";
+
+pub fn reports_router() -> Router<Arc<Database>> {
+    Router::new()
+        .route("/estimates", get(get_estimate_report))
+        .route("/closed_todos", get(get_closed_todos))
+}
+
+async fn get_estimate_report(
+    _: Claims,
+    Timezone(tz): Timezone,
+    params: Query<RangeParams>,
+    db: State<Arc<Database>>,
+) -> Result<Json<EstimateReport>, AppError> {
+    tracing::info!(
+        "Getting estimate report between: {:?} and {:?}",
+        params.get_start(tz),
+        params.get_end(tz)
+    );
+    let mut conn = db.pool.acquire().await?;
+    let rounding = load_settings(&mut conn).await?.rounding;
+
+    let blocks_query = format!(
+        "
+    WITH {ROUNDED_BLOCKS}
+    SELECT
+        blocks.block_id,
+        blocks.text,
+        blocks.project,
+        projects.name AS project_name,
+        blocks.start,
+        blocks.estimate,
+        rounded_blocks.duration,
+        blocks.duration AS raw_duration,
+        rounded_blocks.duration - blocks.estimate AS difference
+    FROM blocks
+
+    JOIN rounded_blocks ON rounded_blocks.block_id = blocks.block_id
+    LEFT OUTER JOIN projects ON blocks.project = projects.project_id
+
+    WHERE blocks.start >= DATETIME(?1) AND blocks.start < DATETIME(?2)
+    ORDER BY blocks.start;
+        "
+    );
+    let blocks = sqlx::query_as::<_, BlockEstimate>(&blocks_query)
+        .bind(params.get_start(tz))
+        .bind(params.get_end(tz))
+        .bind(rounding.mode.as_str())
+        .bind(rounding.interval)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let projects_query = format!(
+        "
+    WITH {ROUNDED_BLOCKS}, totals AS (
+        SELECT
+            project,
+            SUM(duration) AS total_duration
+        FROM rounded_blocks
+        GROUP BY project
+    ), in_range AS (
+        SELECT
+            project,
+            COALESCE(SUM(estimate), 0) AS block_estimate,
+            SUM(duration) AS duration
+        FROM rounded_blocks
+        WHERE start >= DATETIME(?1) AND start < DATETIME(?2)
+        GROUP BY project
+    )
+    SELECT
+        projects.project_id,
+        projects.name,
+        projects.estimate,
+        COALESCE(in_range.block_estimate, 0) AS block_estimate,
+        COALESCE(in_range.duration, 0) AS duration,
+        COALESCE(totals.total_duration, 0) AS total_duration,
+        COALESCE(totals.total_duration, 0) - projects.estimate AS difference
+    FROM projects
+
+    LEFT JOIN totals ON totals.project = projects.project_id
+    LEFT JOIN in_range ON in_range.project = projects.project_id
+
+    WHERE in_range.project IS NOT NULL OR projects.estimate IS NOT NULL
+    ORDER BY projects.name;
+        "
+    );
+    let projects = sqlx::query_as::<_, ProjectEstimate>(&projects_query)
+        .bind(params.get_start(tz))
+        .bind(params.get_end(tz))
+        .bind(rounding.mode.as_str())
+        .bind(rounding.interval)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(Json(EstimateReport { blocks, projects }))
+}
+
+/// The todos that were done or cancelled in the range, in the order they were closed.
+async fn get_closed_todos(
+    _: Claims,
+    Timezone(tz): Timezone,
+    params: Query<RangeParams>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<Todo>>, AppError> {
+    tracing::info!(
+        "Getting todos closed between: {:?} and {:?}",
+        params.get_start(tz),
+        params.get_end(tz)
+    );
+    Ok(Json(
+        sqlx::query_as::<_, Todo>(
+            "
+    SELECT
+        entries.entry_id,
+        entries.parent,
+        entries.parent_entry,
+        entries.nesting,
+        entries.text,
+        entries.todo_state,
+        entries.closed_at,
+        entries.position,
+        entries.due_date,
+        entries.scheduled_date,
+        entries.migrated_from,
+        COALESCE(GROUP_CONCAT(DISTINCT tags.name), '') AS tags,
+        blocks.text AS block_text,
+        blocks.start AS block_start,
+        blocks.project,
+        projects.name AS project_name
+    FROM entries
+
+    JOIN blocks ON entries.parent = blocks.block_id
+    LEFT OUTER JOIN projects ON blocks.project = projects.project_id
+    LEFT JOIN tagged_entries ON entries.entry_id = tagged_entries.entry_fk
+    LEFT JOIN tags ON tagged_entries.tag_fk = tags.tag_id
+
+    WHERE entries.closed_at >= DATETIME(?1) AND entries.closed_at < DATETIME(?2)
+    AND entries.deleted_at IS NULL AND blocks.deleted_at IS NULL
+    GROUP BY entries.entry_id
+    ORDER BY entries.closed_at;
+        ",
+        )
+        .bind(params.get_start(tz))
+        .bind(params.get_end(tz))
+        .fetch_all(&db.pool)
+        .await?,
+    ))
+}
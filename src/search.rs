@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use crate::{
+    auth::Claims,
+    database::Database,
+    errors::AppError,
+    models::{SearchHit, SearchParams, TagParams},
+};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+
+pub fn search_router() -> Router<Arc<Database>> {
+    Router::new().route("/", get(search))
+}
+
+/// Searches the texts of blocks and entries. Blocks come before entries, as the relevance of
+/// hits in the two indexes can not be compared, and the most relevant hits come first within
+/// each. A tag filter keeps the blocks and entries that have one of the tags themselves.
+async fn search(
+    _: Claims,
+    params: Query<SearchParams>,
+    tag_params: Query<TagParams>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<SearchHit>>, AppError> {
+    tracing::info!(
+        "Search for: {:?} with tags: {:?}",
+        params,
+        tag_params.get_tags_json()
+    );
+    let match_query = params.get_match_query().ok_or(AppError::BadRequest)?;
+    Ok(Json(
+        sqlx::query_as::<_, SearchHit>(
+            "
+    WITH hits AS (
+        SELECT
+            'block' AS record_type,
+            blocks_search.rowid AS record_id,
+            blocks_search.rowid AS block_id,
+            snippet(blocks_search, 0, char(2), char(3), '…', 16) AS snippet,
+            bm25(blocks_search) AS rank
+        FROM blocks_search
+        WHERE blocks_search MATCH ?1
+
+        UNION ALL
+
+        SELECT
+            'entry' AS record_type,
+            entries_search.rowid AS record_id,
+            entries.parent AS block_id,
+            snippet(entries_search, 0, char(2), char(3), '…', 16) AS snippet,
+            bm25(entries_search) AS rank
+        FROM entries_search
+        JOIN entries ON entries.entry_id = entries_search.rowid
+        WHERE entries_search MATCH ?1 AND entries.deleted_at IS NULL
+    )
+    SELECT
+        hits.record_type,
+        hits.record_id,
+        hits.snippet,
+        hits.rank,
+        blocks.block_id,
+        blocks.text AS block_text,
+        blocks.start AS block_start,
+        blocks.project,
+        projects.name AS project_name
+    FROM hits
+
+    JOIN blocks ON blocks.block_id = hits.block_id
+    LEFT OUTER JOIN projects ON blocks.project = projects.project_id
+
+    WHERE blocks.deleted_at IS NULL
+    AND (?2 IS NULL OR blocks.project = ?2)
+    AND (?3 IS NULL OR blocks.start >= DATETIME(?3))
+    AND (?4 IS NULL OR blocks.start < DATETIME(?4))
+    AND (?5 IS NULL OR (hits.record_type = 'block' AND hits.record_id IN (
+        SELECT tagged_blocks.block_fk FROM tagged_blocks
+        JOIN tags ON tagged_blocks.tag_fk = tags.tag_id
+        WHERE tags.name IN (SELECT value FROM json_each(?5))
+    )) OR (hits.record_type = 'entry' AND hits.record_id IN (
+        SELECT tagged_entries.entry_fk FROM tagged_entries
+        JOIN tags ON tagged_entries.tag_fk = tags.tag_id
+        WHERE tags.name IN (SELECT value FROM json_each(?5))
+    )))
+    ORDER BY hits.record_type = 'entry', hits.rank, blocks.start DESC
+    LIMIT ?6;
+        ",
+        )
+        .bind(match_query)
+        .bind(params.project)
+        .bind(params.start)
+        .bind(params.end)
+        .bind(tag_params.get_tags_json())
+        .bind(params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .fetch_all(&db.pool)
+        .await?
+        .into_iter()
+        .map(|hit| SearchHit {
+            snippet: highlight(&hit.snippet),
+            ..hit
+        })
+        .collect(),
+    ))
+}
+
+/// Turns a snippet with its matches between the control characters 2 and 3 into HTML: the
+/// text is escaped and the matches are wrapped in `<mark>` tags.
+fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            '\u{2}' => html.push_str("<mark>"),
+            '\u{3}' => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, Query, State},
+    http::request::Parts,
+    routing::get,
+    Json, Router,
+};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlx::SqliteConnection;
+
+use crate::{auth::Claims, database::Database, errors::AppError, models::Settings};
+
+pub fn settings_router() -> Router<Arc<Database>> {
+    Router::new().route("/", get(get_settings).put(put_settings))
+}
+
+async fn get_settings(_: Claims, db: State<Arc<Database>>) -> Result<Json<Settings>, AppError> {
+    let mut conn = db.pool.acquire().await?;
+    Ok(Json(load_settings(&mut conn).await?))
+}
+
+/// Changes the settings that are given and leaves all others as they are. Unknown settings and
+/// values that do not fit a setting are rejected.
+async fn put_settings(
+    _: Claims,
+    db: State<Arc<Database>>,
+    axum::extract::Json(changes): axum::extract::Json<Map<String, Value>>,
+) -> Result<Json<Settings>, AppError> {
+    tracing::info!("Put settings: {:?}", changes);
+    let mut tx = db.pool.begin().await?;
+    let Value::Object(mut values) = serde_json::to_value(load_settings(&mut tx).await?)
+        .map_err(|_| AppError::InternalServer)?
+    else {
+        return Err(AppError::InternalServer);
+    };
+    for (key, value) in &changes {
+        if !values.contains_key(key) {
+            return Err(AppError::BadRequest);
+        }
+        values.insert(key.clone(), value.clone());
+    }
+    let settings: Settings =
+        serde_json::from_value(Value::Object(values)).map_err(|_| AppError::BadRequest)?;
+    let Value::Object(values) =
+        serde_json::to_value(&settings).map_err(|_| AppError::InternalServer)?
+    else {
+        return Err(AppError::InternalServer);
+    };
+    for key in changes.keys() {
+        sqlx::query(
+            "
+    INSERT INTO settings (key, value) VALUES (?1, ?2)
+    ON CONFLICT (key) DO UPDATE SET value = excluded.value;
+            ",
+        )
+        .bind(key)
+        .bind(values[key].to_string())
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Json(settings))
+}
+
+/// Reads the settings, every setting is stored as a JSON value under its own key. Settings
+/// that are missing or can no longer be read fall back to their defaults.
+pub(crate) async fn load_settings(conn: &mut SqliteConnection) -> Result<Settings, AppError> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "
+    SELECT key, value FROM settings;
+        ",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let values: Map<String, Value> = rows
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, serde_json::from_str(&value).ok()?)))
+        .collect();
+    Ok(
+        serde_json::from_value(Value::Object(values)).unwrap_or_else(|error| {
+            tracing::warn!("Could not read settings, using defaults: {}", error);
+            Settings::default()
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct TimezoneParams {
+    tz: Option<String>,
+}
+
+/// The timezone of the request: the `tz` query parameter when given, otherwise the timezone
+/// from the settings.
+pub struct Timezone(pub Tz);
+
+impl FromRequestParts<Arc<Database>> for Timezone {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        db: &Arc<Database>,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(params) =
+            Query::<TimezoneParams>::try_from_uri(&parts.uri).map_err(|_| AppError::BadRequest)?;
+        if let Some(tz) = params.tz {
+            return tz.parse().map(Timezone).map_err(|_| AppError::BadRequest);
+        }
+        let mut conn = db.pool.acquire().await?;
+        Ok(Timezone(load_settings(&mut conn).await?.timezone))
+    }
+}
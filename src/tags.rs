@@ -0,0 +1,425 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    routing::{get, post, put},
+    Json, Router,
+};
+use sqlx::SqliteConnection;
+
+use crate::{
+    auth::Claims,
+    database::Database,
+    errors::AppError,
+    models::{InsertResult, Tag},
+};
+
+pub fn tags_router() -> Router<Arc<Database>> {
+    Router::new()
+        .route("/", get(get_tags).post(post_tag))
+        .route("/{tag_id}", put(put_tag))
+        .route("/{tag_id}/archive", post(archive_tag))
+}
+
+async fn get_tags(_: Claims, db: State<Arc<Database>>) -> Result<Json<Vec<Tag>>, AppError> {
+    Ok(Json(
+        sqlx::query_as::<_, Tag>(
+            "
+        SELECT
+            tag_id,
+            name,
+            archived
+        FROM tags
+        ORDER BY name;
+            ",
+        )
+        .fetch_all(&db.pool)
+        .await?,
+    ))
+}
+
+async fn post_tag(
+    _: Claims,
+    db: State<Arc<Database>>,
+    axum::extract::Json(tag): axum::extract::Json<Tag>,
+) -> Result<Json<Tag>, AppError> {
+    tracing::info!("Post new tag: {:?}", tag);
+    if tag.name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+    let new_tag_id = insert_tag(&db, &tag).await?;
+    select_tag(&db, new_tag_id).await
+}
+
+async fn put_tag(
+    _: Claims,
+    Path(tag_id): Path<i64>,
+    db: State<Arc<Database>>,
+    axum::extract::Json(tag): axum::extract::Json<Tag>,
+) -> Result<Json<Tag>, AppError> {
+    if tag_id != tag.tag_id || tag.name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+    tracing::info!("Put tag: {:?}", tag_id);
+    sqlx::query(
+        "
+    UPDATE tags SET
+        name=?2,
+        archived=?3
+    WHERE tag_id=?1;
+        ",
+    )
+    .bind(tag.tag_id)
+    .bind(tag.name.trim())
+    .bind(tag.archived)
+    .execute(&db.pool)
+    .await?;
+
+    select_tag(&db, tag.tag_id).await
+}
+
+async fn archive_tag(
+    _: Claims,
+    Path(tag_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Tag>, AppError> {
+    tracing::info!("Archive tag: {:?}", tag_id);
+    sqlx::query(
+        "
+    UPDATE tags SET archived=TRUE WHERE tag_id=?1;
+        ",
+    )
+    .bind(tag_id)
+    .execute(&db.pool)
+    .await?;
+
+    select_tag(&db, tag_id).await
+}
+
+async fn select_tag(db: &Database, tag_id: i64) -> Result<Json<Tag>, AppError> {
+    Ok(Json(
+        sqlx::query_as::<_, Tag>(
+            "
+    SELECT
+        tag_id,
+        name,
+        archived
+    FROM tags WHERE tag_id = ?1;
+        ",
+        )
+        .bind(tag_id)
+        .fetch_one(&db.pool)
+        .await?,
+    ))
+}
+
+async fn insert_tag(db: &Database, tag: &Tag) -> Result<i64, AppError> {
+    let new_tag_id = sqlx::query_as::<_, InsertResult>(
+        "
+    INSERT INTO tags (
+        name,
+        archived
+    ) VALUES (
+        ?1,
+        ?2
+    ) RETURNING tag_id AS id;
+        ",
+    )
+    .bind(tag.name.trim())
+    .bind(tag.archived)
+    .fetch_one(&db.pool)
+    .await?;
+    Ok(new_tag_id.id)
+}
+
+pub(crate) async fn tag_id_for_name(
+    conn: &mut SqliteConnection,
+    name: &str,
+) -> Result<i64, AppError> {
+    let existing = sqlx::query_as::<_, InsertResult>(
+        "
+    SELECT tag_id AS id FROM tags WHERE name = ?1 ORDER BY tag_id LIMIT 1;
+        ",
+    )
+    .bind(name)
+    .fetch_optional(&mut *conn)
+    .await?;
+    if let Some(existing) = existing {
+        return Ok(existing.id);
+    }
+
+    let new_tag_id = sqlx::query_as::<_, InsertResult>(
+        "
+    INSERT INTO tags (
+        name,
+        archived
+    ) VALUES (
+        ?1,
+        FALSE
+    ) RETURNING tag_id AS id;
+        ",
+    )
+    .bind(name)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(new_tag_id.id)
+}
+
+pub(crate) async fn tag_block(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+    tag_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    INSERT OR IGNORE INTO tagged_blocks (block_fk, tag_fk) VALUES (?1, ?2);
+        ",
+    )
+    .bind(block_id)
+    .bind(tag_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn untag_block(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+    tag_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    DELETE FROM tagged_blocks WHERE block_fk = ?1 AND tag_fk = ?2;
+        ",
+    )
+    .bind(block_id)
+    .bind(tag_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Adds all tags of one block to another block.
+pub(crate) async fn copy_block_tags(
+    conn: &mut SqliteConnection,
+    from_block_id: i64,
+    to_block_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    INSERT OR IGNORE INTO tagged_blocks (block_fk, tag_fk)
+    SELECT ?2, tag_fk FROM tagged_blocks WHERE block_fk = ?1;
+        ",
+    )
+    .bind(from_block_id)
+    .bind(to_block_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Adds all tags of one entry to another entry.
+pub(crate) async fn copy_entry_tags(
+    conn: &mut SqliteConnection,
+    from_entry_id: i64,
+    to_entry_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    INSERT OR IGNORE INTO tagged_entries (entry_fk, tag_fk)
+    SELECT ?2, tag_fk FROM tagged_entries WHERE entry_fk = ?1;
+        ",
+    )
+    .bind(from_entry_id)
+    .bind(to_entry_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Replaces the tags of a block with the given tag names, creating tags that do not exist yet.
+pub(crate) async fn set_block_tags(
+    conn: &mut SqliteConnection,
+    block_id: i64,
+    tags: &[String],
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    DELETE FROM tagged_blocks WHERE block_fk = ?1;
+        ",
+    )
+    .bind(block_id)
+    .execute(&mut *conn)
+    .await?;
+    for name in tags
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+    {
+        let tag_id = tag_id_for_name(conn, name).await?;
+        tag_block(conn, block_id, tag_id).await?;
+    }
+    Ok(())
+}
+
+/// Replaces the tags of an entry with the given tag names, creating tags that do not exist yet.
+pub(crate) async fn set_entry_tags(
+    conn: &mut SqliteConnection,
+    entry_id: i64,
+    tags: &[String],
+) -> Result<(), AppError> {
+    sqlx::query(
+        "
+    DELETE FROM tagged_entries WHERE entry_fk = ?1;
+        ",
+    )
+    .bind(entry_id)
+    .execute(&mut *conn)
+    .await?;
+    for name in tags
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+    {
+        let tag_id = tag_id_for_name(conn, name).await?;
+        sqlx::query(
+            "
+    INSERT OR IGNORE INTO tagged_entries (entry_fk, tag_fk) VALUES (?1, ?2);
+            ",
+        )
+        .bind(entry_id)
+        .bind(tag_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Returns the `#hashtags` in a text, in order of first appearance and without duplicates.
+///
+/// A hashtag starts at a `#` that is not preceded by a letter, digit or another `#`, and runs
+/// for as long as it is followed by letters, digits, `-`, `_` or `/`.
+pub fn extract_hashtags(text: &str) -> Vec<String> {
+    let is_tag_char = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '/');
+    let mut hashtags: Vec<String> = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '#' && !previous.is_some_and(|p| p.is_alphanumeric() || p == '#') {
+            let mut end = start + c.len_utf8();
+            while let Some(&(index, next)) = chars.peek() {
+                if !is_tag_char(next) {
+                    break;
+                }
+                end = index + next.len_utf8();
+                chars.next();
+            }
+            let hashtag = text[start..end].trim_end_matches(['-', '_', '/']);
+            if hashtag.len() > 1 && !hashtags.iter().any(|h| h == hashtag) {
+                hashtags.push(hashtag.to_string());
+            }
+            previous = text[..end].chars().next_back();
+        } else {
+            previous = Some(c);
+        }
+    }
+    hashtags
+}
+
+/// Combines explicitly given tags with the hashtags in a text. Hashtags that were in the old
+/// text but no longer are in the new one are dropped, even when the client sends them back.
+pub(crate) fn merge_text_tags(
+    tags: &[String],
+    old_text: Option<&str>,
+    new_text: &str,
+) -> Vec<String> {
+    let old_hashtags = old_text.map(extract_hashtags).unwrap_or_default();
+    let new_hashtags = extract_hashtags(new_text);
+    let mut merged: Vec<String> = Vec::new();
+    for tag in tags
+        .iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .filter(|tag| {
+            !old_hashtags.iter().any(|h| h == tag) || new_hashtags.iter().any(|h| h == tag)
+        })
+        .map(str::to_string)
+        .chain(new_hashtags.iter().cloned())
+    {
+        if !merged.contains(&tag) {
+            merged.push(tag);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn hashtags_end_before_punctuation() {
+        assert_eq!(
+            extract_hashtags("Done with #work, then #home. (#later) #end!"),
+            tags(&["#work", "#home", "#later", "#end"])
+        );
+        assert_eq!(
+            extract_hashtags("#a-b_c/d- #trail_"),
+            tags(&["#a-b_c/d", "#trail"])
+        );
+    }
+
+    #[test]
+    fn hash_inside_a_word_is_no_hashtag() {
+        assert_eq!(extract_hashtags("C#sharp issue#12 ##double"), tags(&[]));
+        assert_eq!(extract_hashtags("# alone and #"), tags(&[]));
+    }
+
+    #[test]
+    fn duplicate_hashtags_are_listed_once() {
+        assert_eq!(
+            extract_hashtags("#work and #play and #work again"),
+            tags(&["#work", "#play"])
+        );
+    }
+
+    #[test]
+    fn hashtags_keep_their_case() {
+        assert_eq!(
+            extract_hashtags("#Work #work #Über"),
+            tags(&["#Work", "#work", "#Über"])
+        );
+    }
+
+    #[test]
+    fn merge_keeps_explicit_tags_and_adds_hashtags() {
+        assert_eq!(
+            merge_text_tags(&tags(&["#manual", " ", "#work"]), None, "at #work on #code"),
+            tags(&["#manual", "#work", "#code"])
+        );
+    }
+
+    #[test]
+    fn merge_drops_hashtags_removed_from_the_text() {
+        assert_eq!(
+            merge_text_tags(
+                &tags(&["#manual", "#old", "#kept"]),
+                Some("#old and #kept"),
+                "only #kept"
+            ),
+            tags(&["#manual", "#kept"])
+        );
+    }
+
+    #[test]
+    fn merge_is_case_sensitive() {
+        assert_eq!(
+            merge_text_tags(&tags(&["#Work"]), Some("#Work"), "#work"),
+            tags(&["#work"])
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::{
+    auth::Claims,
+    blocks::select_current_block_id,
+    database::Database,
+    entries::select_entry,
+    errors::AppError,
+    models::{Entry, TagParams, Todo, TodoParams, TodoTransition},
+    tags::copy_entry_tags,
+};
+
+pub fn todos_router() -> Router<Arc<Database>> {
+    Router::new()
+        .route("/", get(get_todos))
+        .route("/migrate", post(migrate_todos))
+        .route("/{entry_id}/transitions", get(get_transitions))
+}
+
+/// Lists the todos of all blocks that are still to be done, or those in the requested state.
+/// Todos with a due date come first, the ones due soonest at the top. When a due window is
+/// given, only todos due within it are listed.
+async fn get_todos(
+    _: Claims,
+    params: Query<TodoParams>,
+    tag_params: Query<TagParams>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<Todo>>, AppError> {
+    tracing::info!(
+        "Getting todos with: {:?} and tags: {:?}",
+        params,
+        tag_params.get_tags_json()
+    );
+    Ok(Json(
+        sqlx::query_as::<_, Todo>(
+            "
+    SELECT
+        entries.entry_id,
+        entries.parent,
+        entries.parent_entry,
+        entries.nesting,
+        entries.text,
+        entries.todo_state,
+        entries.closed_at,
+        entries.position,
+        entries.due_date,
+        entries.scheduled_date,
+        entries.migrated_from,
+        COALESCE(GROUP_CONCAT(DISTINCT tags.name), '') AS tags,
+        blocks.text AS block_text,
+        blocks.start AS block_start,
+        blocks.project,
+        projects.name AS project_name
+    FROM entries
+
+    JOIN blocks ON entries.parent = blocks.block_id
+    LEFT OUTER JOIN projects ON blocks.project = projects.project_id
+    LEFT JOIN tagged_entries ON entries.entry_id = tagged_entries.entry_fk
+    LEFT JOIN tags ON tagged_entries.tag_fk = tags.tag_id
+
+    WHERE (CASE WHEN ?5 IS NULL
+        THEN entries.todo_state IN ('open', 'in_progress', 'blocked')
+        ELSE entries.todo_state = ?5
+    END)
+    AND entries.deleted_at IS NULL AND blocks.deleted_at IS NULL
+    AND (?1 IS NULL OR blocks.project = ?1)
+    AND (?2 IS NULL OR entries.due_date >= ?2)
+    AND (?3 IS NULL OR entries.due_date <= ?3)
+    AND (?4 IS NULL OR entries.entry_id IN (
+        SELECT filter_tagged.entry_fk FROM tagged_entries AS filter_tagged
+        JOIN tags AS filter_tags ON filter_tagged.tag_fk = filter_tags.tag_id
+        WHERE filter_tags.name IN (SELECT value FROM json_each(?4))
+    ))
+
+    GROUP BY entries.entry_id
+    ORDER BY
+        entries.due_date IS NULL,
+        entries.due_date,
+        entries.scheduled_date IS NULL,
+        entries.scheduled_date,
+        blocks.start,
+        entries.position;
+        ",
+        )
+        .bind(params.project)
+        .bind(params.due_from)
+        .bind(params.due_until)
+        .bind(tag_params.get_tags_json())
+        .bind(params.state)
+        .fetch_all(&db.pool)
+        .await?,
+    ))
+}
+
+/// Carries the todos of earlier blocks that are still to be done over to the running block.
+/// Every todo gets a copy in the same state at the end of the running block that links back to
+/// it, and is itself marked as migrated.
+/// Todos nested under another todo that is migrated as well travel as a group with it: their
+/// copies are nested under the copy of their closest migrated ancestor, keeping their order.
+/// Other todos are copied to the top level of the running block. Entries that are no todo or
+/// that are closed stay behind.
+/// Returns the copies.
+async fn migrate_todos(_: Claims, db: State<Arc<Database>>) -> Result<Json<Vec<Entry>>, AppError> {
+    let mut tx = db.pool.begin().await?;
+    let current_block_id = select_current_block_id(&mut tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    tracing::info!("Migrating open todos to block: {}", current_block_id);
+    let open_todos = sqlx::query_as::<_, (i64, Option<i64>)>(
+        "
+    WITH RECURSIVE open_todos AS (
+        SELECT entries.entry_id, entries.parent_entry, blocks.start, entries.position
+        FROM entries
+        JOIN blocks ON entries.parent = blocks.block_id
+        JOIN blocks AS current ON current.block_id = ?1
+        WHERE entries.todo_state IN ('open', 'in_progress', 'blocked')
+        AND entries.deleted_at IS NULL AND blocks.deleted_at IS NULL
+        AND blocks.start < current.start
+    ),
+    ancestors(entry_id, ancestor, depth) AS (
+        SELECT entry_id, parent_entry, 1 FROM open_todos WHERE parent_entry IS NOT NULL
+        UNION ALL
+        SELECT ancestors.entry_id, entries.parent_entry, ancestors.depth + 1 FROM ancestors
+        JOIN entries ON entries.entry_id = ancestors.ancestor
+        WHERE entries.parent_entry IS NOT NULL
+    )
+    SELECT
+        open_todos.entry_id,
+        (SELECT ancestors.ancestor FROM ancestors
+            WHERE ancestors.entry_id = open_todos.entry_id
+            AND ancestors.ancestor IN (SELECT entry_id FROM open_todos)
+            ORDER BY ancestors.depth
+            LIMIT 1) AS migrated_ancestor
+    FROM open_todos
+    ORDER BY open_todos.start, open_todos.position, open_todos.entry_id;
+        ",
+    )
+    .bind(current_block_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Todos come in document order, so the copy of an ancestor always exists before the copies
+    // of its descendants, and appending each copy keeps every group together.
+    let mut copies: Vec<i64> = Vec::with_capacity(open_todos.len());
+    let mut copy_of: HashMap<i64, (i64, i64)> = HashMap::new();
+    for (original, migrated_ancestor) in open_todos {
+        let (parent_entry, nesting) = match migrated_ancestor.and_then(|id| copy_of.get(&id)) {
+            Some((parent_copy, parent_nesting)) => (Some(*parent_copy), parent_nesting + 1),
+            None => (None, 0),
+        };
+        let (copy,) = sqlx::query_as::<_, (i64,)>(
+            "
+    INSERT INTO entries (
+        parent,
+        parent_entry,
+        nesting,
+        text,
+        todo_state,
+        due_date,
+        scheduled_date,
+        migrated_from,
+        position,
+        updated_at
+    )
+    SELECT
+        ?2,
+        ?3,
+        ?4,
+        text,
+        todo_state,
+        due_date,
+        scheduled_date,
+        entry_id,
+        (SELECT COALESCE(MAX(position) + 1, 0) FROM entries WHERE parent = ?2),
+        DATETIME('now')
+    FROM entries WHERE entry_id = ?1
+    RETURNING entry_id;
+            ",
+        )
+        .bind(original)
+        .bind(current_block_id)
+        .bind(parent_entry)
+        .bind(nesting)
+        .fetch_one(&mut *tx)
+        .await?;
+        copy_entry_tags(&mut tx, original, copy).await?;
+        sqlx::query(
+            "
+    UPDATE entries SET todo_state = 'migrated' WHERE entry_id = ?1;
+            ",
+        )
+        .bind(original)
+        .execute(&mut *tx)
+        .await?;
+        copy_of.insert(original, (copy, nesting));
+        copies.push(copy);
+    }
+
+    let mut migrated = Vec::with_capacity(copies.len());
+    for copy in copies {
+        migrated.push(select_entry(&mut *tx, copy).await?.0);
+    }
+    tx.commit().await?;
+
+    Ok(Json(migrated))
+}
+
+/// The changes of the todo state of an entry, oldest first.
+async fn get_transitions(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Vec<TodoTransition>>, AppError> {
+    tracing::info!("Get todo transitions of entry: {}", entry_id);
+    Ok(Json(
+        sqlx::query_as::<_, TodoTransition>(
+            "
+    SELECT
+        transition_id,
+        entry_fk AS entry_id,
+        from_state,
+        to_state,
+        changed_at,
+        backfilled
+    FROM todo_transitions
+    WHERE entry_fk = ?1
+    ORDER BY changed_at, transition_id;
+        ",
+        )
+        .bind(entry_id)
+        .fetch_all(&db.pool)
+        .await?,
+    ))
+}
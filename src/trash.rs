@@ -0,0 +1,237 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use sqlx::SqliteConnection;
+
+use crate::{
+    auth::Claims,
+    blocks::{resolve_overlaps, select_block, BlockSpan},
+    database::Database,
+    entries::{select_entry, update_subtree_nesting},
+    errors::AppError,
+    models::{Block, Entry, OverlapMode, Trash, TrashedBlock, TrashedEntry},
+    settings::load_settings,
+};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn trash_router() -> Router<Arc<Database>> {
+    Router::new()
+        .route("/", get(get_trash).delete(empty_trash))
+        .route("/blocks/{block_id}", delete(purge_block))
+        .route("/blocks/{block_id}/restore", post(restore_block))
+        .route("/entries/{entry_id}", delete(purge_entry))
+        .route("/entries/{entry_id}/restore", post(restore_entry))
+}
+
+async fn get_trash(_: Claims, db: State<Arc<Database>>) -> Result<Json<Trash>, AppError> {
+    tracing::info!("Get trash");
+    let blocks = sqlx::query_as::<_, TrashedBlock>(
+        "
+    SELECT
+        block_id,
+        text,
+        project,
+        start,
+        end,
+        deleted_at
+    FROM blocks
+    WHERE deleted_at IS NOT NULL
+    ORDER BY deleted_at DESC;
+        ",
+    )
+    .fetch_all(&db.pool)
+    .await?;
+    let entries = sqlx::query_as::<_, TrashedEntry>(
+        "
+    SELECT
+        entry_id,
+        parent,
+        text,
+        deleted_at
+    FROM entries
+    WHERE deleted_at IS NOT NULL
+    ORDER BY deleted_at DESC;
+        ",
+    )
+    .fetch_all(&db.pool)
+    .await?;
+    Ok(Json(Trash { blocks, entries }))
+}
+
+async fn restore_block(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Block>, AppError> {
+    tracing::info!("Restore block: {}", block_id);
+    let mut tx = db.pool.begin().await?;
+    let span = sqlx::query_as::<_, (i64, _, _)>(
+        "
+    SELECT block_id, start, end FROM blocks
+    WHERE block_id = ?1 AND deleted_at IS NOT NULL;
+        ",
+    )
+    .bind(block_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map(|(block_id, start, end)| BlockSpan {
+        block_id: Some(block_id),
+        start,
+        end,
+    })?;
+    resolve_overlaps(&mut tx, &span, None, OverlapMode::Reject).await?;
+    sqlx::query(
+        "
+    UPDATE blocks SET deleted_at = NULL WHERE block_id = ?1;
+        ",
+    )
+    .bind(block_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    select_block(&db.pool, block_id).await
+}
+
+/// Restores an entry together with the entries under it that were deleted along with it. An
+/// entry whose parent entry is still in the trash ends up at the top of its block.
+async fn restore_entry(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<Json<Entry>, AppError> {
+    tracing::info!("Restore entry: {}", entry_id);
+    let mut tx = db.pool.begin().await?;
+    let restored = sqlx::query(
+        "
+    WITH RECURSIVE subtree(entry_id, deleted_at) AS (
+        SELECT entry_id, deleted_at FROM entries WHERE entry_id = ?1 AND deleted_at IS NOT NULL
+        UNION ALL
+        SELECT entries.entry_id, entries.deleted_at FROM entries
+        JOIN subtree ON entries.parent_entry = subtree.entry_id
+        WHERE entries.deleted_at = subtree.deleted_at
+    )
+    UPDATE entries SET deleted_at = NULL
+    WHERE entry_id IN (SELECT entry_id FROM subtree);
+        ",
+    )
+    .bind(entry_id)
+    .execute(&mut *tx)
+    .await?;
+    if restored.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    sqlx::query(
+        "
+    UPDATE entries SET parent_entry = NULL
+    WHERE entry_id = ?1 AND parent_entry IN (
+        SELECT entry_id FROM entries WHERE deleted_at IS NOT NULL
+    );
+        ",
+    )
+    .bind(entry_id)
+    .execute(&mut *tx)
+    .await?;
+    update_subtree_nesting(&mut tx, entry_id).await?;
+    tx.commit().await?;
+
+    select_entry(&db.pool, entry_id).await
+}
+
+async fn purge_block(
+    _: Claims,
+    Path(block_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<StatusCode, AppError> {
+    tracing::info!("Purge block: {}", block_id);
+    let purged = sqlx::query(
+        "
+    DELETE FROM blocks WHERE block_id = ?1 AND deleted_at IS NOT NULL;
+        ",
+    )
+    .bind(block_id)
+    .execute(&db.pool)
+    .await?;
+    if purged.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn purge_entry(
+    _: Claims,
+    Path(entry_id): Path<i64>,
+    db: State<Arc<Database>>,
+) -> Result<StatusCode, AppError> {
+    tracing::info!("Purge entry: {}", entry_id);
+    let purged = sqlx::query(
+        "
+    DELETE FROM entries WHERE entry_id = ?1 AND deleted_at IS NOT NULL;
+        ",
+    )
+    .bind(entry_id)
+    .execute(&db.pool)
+    .await?;
+    if purged.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn empty_trash(_: Claims, db: State<Arc<Database>>) -> Result<StatusCode, AppError> {
+    tracing::info!("Empty trash");
+    let mut conn = db.pool.acquire().await?;
+    purge_deleted_before(&mut conn, 0).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Periodically removes blocks and entries that are in the trash for longer than the retention
+/// period from the settings.
+pub async fn run(db: Arc<Database>) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let purged = match db.pool.acquire().await {
+            Ok(mut conn) => match load_settings(&mut conn).await {
+                Ok(settings) => {
+                    purge_deleted_before(&mut conn, settings.trash_retention_days).await
+                }
+                Err(error) => Err(error),
+            },
+            Err(error) => Err(error.into()),
+        };
+        match purged {
+            Ok(0) => {}
+            Ok(purged) => tracing::info!("Purged {} items from the trash", purged),
+            Err(error) => tracing::warn!("Purging the trash failed: {:?}", error),
+        }
+    }
+}
+
+/// Removes the blocks and entries that were deleted more than `days` ago, returns how many.
+async fn purge_deleted_before(conn: &mut SqliteConnection, days: u32) -> Result<u64, AppError> {
+    let cutoff = format!("-{days} days");
+    let entries = sqlx::query(
+        "
+    DELETE FROM entries WHERE deleted_at <= DATETIME('now', ?1);
+        ",
+    )
+    .bind(&cutoff)
+    .execute(&mut *conn)
+    .await?;
+    let blocks = sqlx::query(
+        "
+    DELETE FROM blocks WHERE deleted_at <= DATETIME('now', ?1);
+        ",
+    )
+    .bind(&cutoff)
+    .execute(&mut *conn)
+    .await?;
+    Ok(entries.rows_affected() + blocks.rows_affected())
+}